@@ -1,7 +1,18 @@
-#version 460 core
-layout (location=0) in vec3 color;
+#version 450 core
+// features: TEXTURED multiplies base_color_factor by texture0, otherwise base_color_factor is drawn on its own
+
+#include "per_frame_data.glsl"
+
+#ifdef TEXTURED
+layout (location=0) in vec2 uv;
+uniform sampler2D texture0;
+#endif
 layout (location=0) out vec4 out_FragColor;
 
 void main() {
-  out_FragColor = vec4(color, 1.0);
+#ifdef TEXTURED
+  out_FragColor = texture(texture0, uv) * base_color_factor;
+#else
+  out_FragColor = wire_frame_enabled > 0u ? vec4(0.0, 0.0, 0.0, 1.0) : base_color_factor;
+#endif
 }
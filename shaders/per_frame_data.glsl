@@ -0,0 +1,6 @@
+// matches `PerFrameData` in renderer/mod.rs, checked by `shaders_match_per_frame_data`
+layout (std140, binding = 0) uniform perFrameData {
+  mat4 perspective_transform;
+  uint wire_frame_enabled;
+  vec4 base_color_factor;
+};
@@ -0,0 +1,4 @@
+// matches the attribute locations set up by `Mesh`
+layout (location=0) in vec3 in_position;
+layout (location=1) in vec3 in_normal;
+layout (location=2) in vec2 in_uv;
@@ -0,0 +1,179 @@
+use std::collections::HashSet;
+
+use log::{debug, warn};
+use winit::event::{DeviceEvent, ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
+use winit::window::{CursorGrabMode, Window};
+
+use super::renderer::Camera;
+
+/// turns keyboard and mouse input into camera movement.
+///
+/// fly: WASD moves, space/shift fly up/down, and the mouse looks around while the cursor is grabbed.
+/// clicking in the window grabs the cursor, escape releases it.
+///
+/// orbit: left drag rotates around the target, middle drag pans and the scroll wheel zooms.
+#[derive(Default)]
+pub struct CameraController {
+    pressed: HashSet<KeyCode>,
+    mouse_delta: (f64, f64),
+    scroll_steps: f32,
+    rotating: bool,
+    panning: bool,
+    pub cursor_grabbed: bool,
+}
+
+/// what a press or release of escape should do
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EscapeAction {
+    ReleaseCursor,
+    Exit,
+    Ignore,
+}
+
+// pixel scroll deltas (touchpads) per line of a mouse wheel
+const PIXELS_PER_SCROLL_STEP: f64 = 50.0;
+
+impl CameraController {
+    /// returns true when the event was used
+    pub fn window_event(&mut self, window: &Window, event: &WindowEvent, camera: &Camera) -> bool {
+        match event {
+            WindowEvent::KeyboardInput {
+                event: KeyEvent {
+                    physical_key: PhysicalKey::Code(code),
+                    state,
+                    ..
+                },
+                ..
+            } => {
+                match state {
+                    ElementState::Pressed => self.pressed.insert(*code),
+                    ElementState::Released => self.pressed.remove(code),
+                };
+                false
+            }
+            WindowEvent::MouseInput { state, button, .. } => match (camera, button) {
+                (Camera::Fly(_), MouseButton::Left) if *state == ElementState::Pressed && !self.cursor_grabbed => {
+                    self.set_cursor_grab(window, true);
+                    true
+                }
+                (Camera::Orbit(_), MouseButton::Left) => {
+                    self.rotating = *state == ElementState::Pressed;
+                    true
+                }
+                (Camera::Orbit(_), MouseButton::Middle) => {
+                    self.panning = *state == ElementState::Pressed;
+                    true
+                }
+                _ => false,
+            },
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.scroll_steps += match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => (position.y / PIXELS_PER_SCROLL_STEP) as f32,
+                };
+                true
+            }
+            // keys and buttons released while unfocused never send a release event
+            WindowEvent::Focused(false) => {
+                self.pressed.clear();
+                self.rotating = false;
+                self.panning = false;
+                self.set_cursor_grab(window, false);
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// escape gives the cursor back first and a second press exits. only fresh presses count, so the
+    /// release of the press that freed the cursor doesn't exit
+    pub fn escape(&self, state: ElementState, repeat: bool) -> EscapeAction {
+        match (state, repeat) {
+            (ElementState::Pressed, false) if self.cursor_grabbed => EscapeAction::ReleaseCursor,
+            (ElementState::Pressed, false) => EscapeAction::Exit,
+            _ => EscapeAction::Ignore,
+        }
+    }
+
+    pub fn device_event(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta: (x, y) } = event {
+            if self.cursor_grabbed || self.rotating || self.panning {
+                self.mouse_delta.0 += x;
+                self.mouse_delta.1 += y;
+            }
+        }
+    }
+
+    pub fn set_cursor_grab(&mut self, window: &Window, grab: bool) {
+        if grab == self.cursor_grabbed {
+            return;
+        }
+
+        let result = match grab {
+            // not every platform can lock, confining still gives us relative motion events
+            true => window.set_cursor_grab(CursorGrabMode::Locked).or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined)),
+            false => window.set_cursor_grab(CursorGrabMode::None),
+        };
+
+        match result {
+            Ok(()) => {
+                debug!("cursor grabbed: {}", grab);
+                window.set_cursor_visible(!grab);
+                self.cursor_grabbed = grab;
+            }
+            Err(e) => warn!("failed to set cursor grab to {}: {}", grab, e),
+        }
+        self.mouse_delta = (0.0, 0.0);
+    }
+
+    /// applies the input gathered since the last frame
+    pub fn update(&mut self, camera: &mut Camera, frame_delta: f32) {
+        let (x, y) = std::mem::take(&mut self.mouse_delta);
+        let (x, y) = (x as f32, y as f32);
+        let scroll_steps = std::mem::take(&mut self.scroll_steps);
+
+        match camera {
+            Camera::Fly(fly) => {
+                let axis = |positive: KeyCode, negative: KeyCode| self.pressed.contains(&positive) as i32 as f32 - self.pressed.contains(&negative) as i32 as f32;
+
+                let direction = glm::vec3(
+                    axis(KeyCode::KeyD, KeyCode::KeyA),
+                    axis(KeyCode::Space, KeyCode::ShiftLeft),
+                    axis(KeyCode::KeyW, KeyCode::KeyS),
+                );
+                fly.fly(direction, frame_delta);
+                fly.look(x, y);
+            }
+            Camera::Orbit(orbit) => {
+                if self.rotating {
+                    orbit.rotate(x, y);
+                } else if self.panning {
+                    orbit.pan(x, y);
+                }
+                orbit.zoom(scroll_steps);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_releases_the_cursor_before_exiting() {
+        let mut controller = CameraController {
+            cursor_grabbed: true,
+            ..Default::default()
+        };
+
+        assert_eq!(controller.escape(ElementState::Pressed, false), EscapeAction::ReleaseCursor);
+        // what `set_cursor_grab` does once the window lets go
+        controller.cursor_grabbed = false;
+        assert_eq!(controller.escape(ElementState::Pressed, true), EscapeAction::Ignore);
+        assert_eq!(controller.escape(ElementState::Released, false), EscapeAction::Ignore);
+
+        assert_eq!(controller.escape(ElementState::Pressed, false), EscapeAction::Exit);
+    }
+}
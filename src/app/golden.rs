@@ -0,0 +1,215 @@
+//! golden image regression tests: frames are rendered headlessly from fixed camera poses and compared against
+//! reference PNGs in `tests/golden`. Run with `THREED_BLESS=1` to (re)write the references after an
+//! intended change, and commit the new images alongside it.
+
+use image::{Rgba, RgbaImage};
+use std::env;
+use std::path::PathBuf;
+
+use super::headless::HeadlessRenderer;
+
+/// how far apart the reference and rendered frames may be before the test fails
+#[derive(Clone, Copy)]
+pub struct Tolerance {
+    /// largest difference allowed in any channel of a pixel before it counts as mismatched
+    pub channel: u8,
+    /// number of mismatched pixels allowed, absorbs rasterisation differences between drivers
+    pub pixels: usize,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Tolerance { channel: 2, pixels: 16 }
+    }
+}
+
+pub struct Comparison {
+    pub mismatched: usize,
+    pub max_difference: u8,
+    pub diff: RgbaImage,
+}
+
+/// per pixel comparison, the diff image shows mismatched pixels in red over a faded copy of `actual`
+pub fn compare(expected: &RgbaImage, actual: &RgbaImage, channel_tolerance: u8) -> Comparison {
+    assert_eq!(expected.dimensions(), actual.dimensions(), "images must be the same size to compare");
+
+    let mut mismatched = 0;
+    let mut max_difference = 0;
+    let mut diff = RgbaImage::new(actual.width(), actual.height());
+
+    for ((expected_pixel, actual_pixel), diff_pixel) in expected.pixels().zip(actual.pixels()).zip(diff.pixels_mut()) {
+        let difference = expected_pixel.0.iter().zip(actual_pixel.0.iter()).map(|(a, b)| a.abs_diff(*b)).max().unwrap();
+        max_difference = max_difference.max(difference);
+
+        *diff_pixel = if difference > channel_tolerance {
+            mismatched += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let [r, g, b, _] = actual_pixel.0;
+            let luma = ((r as u32 + g as u32 + b as u32) / 3) as u8;
+            Rgba([luma / 4, luma / 4, luma / 4, 255])
+        };
+    }
+
+    Comparison {
+        mismatched,
+        max_difference,
+        diff,
+    }
+}
+
+fn reference_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{}.png", name))
+}
+
+fn output_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/golden")
+}
+
+/// compares `actual` against the reference image `name`, writing `<name>.actual.png` and `<name>.diff.png`
+/// to `target/golden` when they don't match
+pub fn assert_golden(name: &str, actual: &RgbaImage, tolerance: Tolerance) {
+    let reference = reference_path(name);
+
+    if env::var_os("THREED_BLESS").is_some() {
+        actual.save(&reference).unwrap_or_else(|e| panic!("failed to write reference {}: {}", reference.display(), e));
+        return;
+    }
+
+    let expected = match image::open(&reference) {
+        Ok(expected) => expected.into_rgba8(),
+        Err(e) => panic!("missing reference {} ({}), rerun with THREED_BLESS=1 to create it", reference.display(), e),
+    };
+
+    if expected.dimensions() != actual.dimensions() {
+        panic!("{}: reference is {:?} but the render is {:?}", name, expected.dimensions(), actual.dimensions());
+    }
+
+    let comparison = compare(&expected, actual, tolerance.channel);
+    if comparison.mismatched <= tolerance.pixels {
+        return;
+    }
+
+    let output_dir = output_dir();
+    std::fs::create_dir_all(&output_dir).unwrap();
+    let actual_path = output_dir.join(format!("{}.actual.png", name));
+    let diff_path = output_dir.join(format!("{}.diff.png", name));
+    actual.save(&actual_path).unwrap();
+    comparison.diff.save(&diff_path).unwrap();
+
+    panic!(
+        "{}: {} pixels differ by more than {} (largest difference {}), see {} and {}",
+        name,
+        comparison.mismatched,
+        tolerance.channel,
+        comparison.max_difference,
+        actual_path.display(),
+        diff_path.display()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::renderer::{Camera, FieldOfView, OrbitCamera, Projection, Radians, Transform};
+    use crate::app::DEFAULT_SCENE;
+
+    fn render_golden(name: &str, camera: Option<Camera>) {
+        render_golden_with(name, camera, None);
+    }
+
+    fn render_golden_with(name: &str, camera: Option<Camera>, projection: Option<Projection>) {
+        let mut headless = HeadlessRenderer::new(128, 128, DEFAULT_SCENE).unwrap();
+        if let Some(camera) = camera {
+            *headless.renderer().camera_mut() = camera;
+        }
+        if let Some(projection) = projection {
+            headless.renderer().set_projection(projection);
+        }
+        let frame = headless.render();
+        assert_golden(name, &frame, Tolerance::default());
+    }
+
+    fn orbit(yaw: f32, pitch: f32) -> Camera {
+        let mut orbit = OrbitCamera::new(glm::Vec3::zeros(), 3.5);
+        orbit.yaw = yaw;
+        orbit.pitch = pitch;
+        Camera::Orbit(orbit)
+    }
+
+    #[test]
+    fn cube_default_view() {
+        render_golden("cube_default", None);
+    }
+
+    #[test]
+    fn cube_orbited() {
+        render_golden("cube_orbit_yaw1_pitch0_5", Some(orbit(1.0, 0.5)));
+    }
+
+    #[test]
+    fn cube_orbited_from_below() {
+        render_golden("cube_orbit_yaw2_5_pitch-0_6", Some(orbit(2.5, -0.6)));
+    }
+
+    #[test]
+    fn cube_orthographic() {
+        let projection = Projection::Orthographic {
+            height: 4.0,
+            near_clipping_plane: 0.1,
+            far_clipping_plane: 100.0,
+        };
+        render_golden_with("cube_orthographic", Some(orbit(0.6, -0.4)), Some(projection));
+    }
+
+    #[test]
+    fn cube_reversed_z() {
+        let projection = Projection::ReversedInfinitePerspective {
+            field_of_view: FieldOfView::Vertical(Radians(1.0)),
+            near_clipping_plane: 0.1,
+        };
+        render_golden_with("cube_reversed_z", Some(orbit(1.0, 0.5)), Some(projection));
+    }
+
+    #[test]
+    fn cube_hierarchy() {
+        let mut headless = HeadlessRenderer::new(128, 128, DEFAULT_SCENE).unwrap();
+        let renderer = headless.renderer();
+
+        // a small cube orbiting the default one, carried by a rotated parent with no mesh of its own
+        let pivot = Transform {
+            rotation: glm::quat_angle_axis(-0.8, &glm::Vec3::y()),
+            ..Default::default()
+        };
+        let scene = renderer.scene_mut();
+        let pivot = scene.add_node("pivot", pivot, None);
+        let moon = Transform {
+            translation: glm::vec3(2.0, 0.5, 0.0),
+            scale: glm::vec3(0.4, 0.4, 0.4),
+            ..Default::default()
+        };
+        let moon = scene.add_node("moon", moon, Some(pivot));
+        scene.set_mesh(moon, Some(0));
+        scene.set_material(moon, Some(0));
+
+        *renderer.camera_mut() = orbit(0.3, -0.3);
+        renderer.frame_object();
+
+        let frame = headless.render();
+        assert_golden("cube_hierarchy", &frame, Tolerance::default());
+    }
+
+    #[test]
+    fn compare_counts_pixels_outside_tolerance() {
+        let expected = RgbaImage::from_pixel(4, 4, Rgba([100, 100, 100, 255]));
+        let mut actual = expected.clone();
+        actual.put_pixel(0, 0, Rgba([102, 100, 100, 255]));
+        actual.put_pixel(3, 3, Rgba([100, 90, 100, 255]));
+
+        let comparison = compare(&expected, &actual, 2);
+        assert_eq!(comparison.mismatched, 1);
+        assert_eq!(comparison.max_difference, 10);
+        assert_eq!(comparison.diff.get_pixel(3, 3), &Rgba([255, 0, 0, 255]));
+        assert_ne!(comparison.diff.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+    }
+}
@@ -0,0 +1,167 @@
+use anyhow::{anyhow, Context, Result};
+use glutin::api::egl::context::PossiblyCurrentContext;
+use glutin::api::egl::device::Device;
+use glutin::api::egl::display::Display;
+use glutin::config::{Api, ConfigSurfaceTypes, ConfigTemplateBuilder};
+use glutin::context::{ContextApi, ContextAttributesBuilder, Version};
+use glutin::display::GlDisplay;
+use image::RgbaImage;
+use log::{debug, info, warn};
+
+#[cfg(test)]
+use super::renderer::gl;
+use super::renderer::{ColorFormat, DepthAttachment, DepthFormat, Framebuffer, FramebufferDescriptor, Renderer, SceneDescription};
+
+/// renders without a window or display server: an EGL device display with a surfaceless context,
+/// drawing into a framebuffer object. Works on Mesa's llvmpipe so it can run on CI machines without a GPU.
+pub struct HeadlessRenderer {
+    // NOTE: GL objects must be deleted before the context they were created on is dropped
+    renderer: Renderer,
+    _context: PossiblyCurrentContext,
+}
+
+impl HeadlessRenderer {
+    /// `scene_file` is loaded once the context exists, see `SceneDescription`
+    pub fn new(width: u32, height: u32, scene_file: &str) -> Result<Self> {
+        let (display, context) = surfaceless_context()?;
+        HeadlessRenderer::with_context(display, context, width, height, scene_file)
+    }
+
+    fn with_context(display: Display, context: PossiblyCurrentContext, width: u32, height: u32, scene_file: &str) -> Result<Self> {
+        let mut renderer = Renderer::new(&display)?;
+        renderer.load_scene(&SceneDescription::load(scene_file)?)?;
+
+        let descriptor = FramebufferDescriptor {
+            color: vec![ColorFormat::RGBA8],
+            depth: Some(DepthAttachment::Renderbuffer(DepthFormat::DEPTH24)),
+        };
+        renderer.set_render_target(Some(Framebuffer::new(descriptor, width as i32, height as i32)?));
+        renderer.resize(width as i32, height as i32);
+
+        info!("created {}x{} headless renderer", width, height);
+
+        Ok(HeadlessRenderer {
+            renderer,
+            _context: context,
+        })
+    }
+
+    /// draws one frame and reads it back, top row first
+    pub fn render(&mut self) -> RgbaImage {
+        self.renderer.draw();
+        self.renderer.read_pixels()
+    }
+
+    #[allow(unused)]
+    pub fn renderer(&mut self) -> &mut Renderer {
+        &mut self.renderer
+    }
+}
+
+/// a current context on the first EGL device that can make one
+fn surfaceless_context() -> Result<(Display, PossiblyCurrentContext)> {
+    let devices = Device::query_devices().context("failed to query EGL devices")?;
+
+    // hardware devices are listed first, llvmpipe (EGL_MESA_device_software) last
+    let mut last_error = anyhow!("no EGL devices found");
+    for device in devices {
+        debug!("trying EGL device {:?} ({:?})", device.name(), device.vendor());
+        match create_surfaceless_context(&device) {
+            Ok(context) => return Ok(context),
+            Err(e) => {
+                warn!("EGL device {:?} unusable: {:#}", device.name(), e);
+                last_error = e;
+            }
+        }
+    }
+
+    Err(last_error.context("failed to create a headless GL context"))
+}
+
+/// a current context with the GL functions loaded but no `Renderer` or scene, for tests of single GL objects.
+/// keep it alive until everything created on it has been dropped
+#[cfg(test)]
+pub fn gl_test_context() -> PossiblyCurrentContext {
+    let (display, context) = surfaceless_context().unwrap();
+    gl::load_with(|symbol| display.get_proc_address(&std::ffi::CString::new(symbol).unwrap()).cast());
+    context
+}
+
+fn create_surfaceless_context(device: &Device) -> Result<(Display, PossiblyCurrentContext)> {
+    let display = unsafe { Display::with_device(device, None) }.context("failed to create EGL display")?;
+
+    let template = ConfigTemplateBuilder::new()
+        .with_alpha_size(8)
+        .with_api(Api::OPENGL)
+        .with_surface_type(ConfigSurfaceTypes::empty())
+        .build();
+    let config = unsafe { display.find_configs(template) }
+        .context("failed to find EGL configs")?
+        .next()
+        .ok_or_else(|| anyhow!("no surfaceless EGL config"))?;
+
+    // DSA needs 4.5, which is also the newest version llvmpipe offers
+    let context_attributes = ContextAttributesBuilder::new().with_context_api(ContextApi::OpenGl(Some(Version::new(4, 5)))).build(None);
+    let context = unsafe { display.create_context(&config, &context_attributes) }.context("failed to create GL 4.5 context")?;
+    let context = context.make_current_surfaceless().context("failed to make context current")?;
+
+    Ok((display, context))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::DEFAULT_SCENE;
+
+    #[test]
+    fn renders_cube_offscreen() {
+        let mut headless = HeadlessRenderer::new(64, 48, DEFAULT_SCENE).unwrap();
+        let image = headless.render();
+
+        assert_eq!(image.dimensions(), (64, 48));
+        // the cube sits in the middle of the frame, the corners are clear colour
+        assert_ne!(image.get_pixel(32, 24), image.get_pixel(0, 0));
+        assert_eq!(image.get_pixel(0, 0), image.get_pixel(63, 47));
+    }
+
+    #[test]
+    fn render_target_follows_resize() {
+        let mut headless = HeadlessRenderer::new(64, 48, DEFAULT_SCENE).unwrap();
+        headless.renderer().resize(32, 16);
+
+        assert_eq!(headless.render().dimensions(), (32, 16));
+    }
+
+    #[test]
+    fn untextured_materials_draw_their_base_color_factor() {
+        let mut headless = HeadlessRenderer::new(16, 16, DEFAULT_SCENE).unwrap();
+        let scene = "(
+            meshes: [(name: \"cube\", path: \"meshes/cube.obj\")],
+            materials: [(name: \"red\", base_color_factor: (1.0, 0.0, 0.0, 1.0))],
+            nodes: [(name: \"cube\", mesh: Some(\"cube\"), material: Some(\"red\"))],
+            camera: Orbit(distance: 3.5),
+        )";
+        headless.renderer().load_scene(&SceneDescription::parse(scene).unwrap()).unwrap();
+
+        let image = headless.render();
+        assert_eq!(image.get_pixel(8, 8).0, [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn draws_instanced_models() {
+        let mut headless = HeadlessRenderer::new(16, 16, DEFAULT_SCENE).unwrap();
+        // the fixture's triangle spans (1, 2) to (3, 4), moved so it covers the middle of the frame
+        let scene = "(
+            models: [(name: \"triangle\", path: \"tests/fixtures/gltf/triangle.gltf\")],
+            nodes: [(name: \"instance\", translation: (-1.5, -2.5, 0.0), model: Some(\"triangle\"))],
+            camera: Orbit(distance: 3.5),
+        )";
+        let description = SceneDescription::parse(scene).unwrap();
+        headless.renderer().load_scene(&description).unwrap();
+
+        let image = headless.render();
+        assert_ne!(image.get_pixel(8, 8), image.get_pixel(0, 0));
+        assert_eq!(headless.renderer().scene_mut().iter().count(), 4);
+        assert_eq!(headless.renderer().scene_description().nodes, description.nodes);
+    }
+}
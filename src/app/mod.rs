@@ -1,14 +1,15 @@
 use std::error::Error;
+use std::f32::consts::FRAC_PI_2;
 use std::num::NonZeroU32;
 
 //use gl::types::GLfloat;
 use raw_window_handle::HasWindowHandle;
-use renderer::{Renderer};
+use renderer::{Camera, Renderer, SceneDescription};
 use winit::application::ApplicationHandler;
-use winit::event::{KeyEvent, WindowEvent};
+use winit::event::{DeviceEvent, DeviceId, ElementState, KeyEvent, WindowEvent};
 use winit::event_loop::EventLoop;
 use winit::event_loop::{ActiveEventLoop, ControlFlow};
-use winit::keyboard::{Key, NamedKey};
+use winit::keyboard::{Key, KeyCode, NamedKey, PhysicalKey};
 use winit::window::Window;
 
 use glutin::config::{Config, ConfigTemplateBuilder, GetGlConfig};
@@ -20,13 +21,36 @@ use glutin::surface::{Surface, SwapInterval, WindowSurface};
 use glutin_winit::{DisplayBuilder, GlWindow};
 
 use std::time::Instant;
-use log::{debug, info, trace, warn};
+use log::{debug, error, info, trace, warn};
 
 mod renderer;
+mod camera_controller;
+use camera_controller::{CameraController, EscapeAction};
+#[cfg(all(any(windows, unix), not(target_vendor = "apple")))]
+mod headless;
+#[cfg(all(test, any(windows, unix), not(target_vendor = "apple")))]
+mod golden;
+
+pub struct ApplicationConfig {
+    pub headless: Option<HeadlessConfig>,
+    /// scene description to load at startup, F5 saves the current state back to it
+    pub scene: String,
+}
+
+pub const DEFAULT_SCENE: &str = "scenes/cube.ron";
 
-pub struct ApplicationConfig {}
+/// render a single frame without opening a window and save it to `output`
+pub struct HeadlessConfig {
+    pub width: u32,
+    pub height: u32,
+    pub output: String,
+}
+
+pub fn main(config: ApplicationConfig) -> Result<(), Box<dyn Error>> {
+    if let Some(headless_config) = config.headless {
+        return run_headless(headless_config, &config.scene);
+    }
 
-pub fn main(_: ApplicationConfig) -> Result<(), Box<dyn Error>> {
     let event_loop = EventLoop::new().unwrap();
 
     let gl_display_config = ConfigTemplateBuilder::new()
@@ -37,7 +61,7 @@ pub fn main(_: ApplicationConfig) -> Result<(), Box<dyn Error>> {
     let window_attributes = Window::default_attributes().with_transparent(true).with_title("hello world!");
     let display_builder = DisplayBuilder::new().with_window_attributes(Some(window_attributes));
 
-    let mut app = App::new(gl_display_config, display_builder);
+    let mut app = App::new(gl_display_config, display_builder, config.scene);
 
     event_loop.set_control_flow(ControlFlow::Poll);
 
@@ -46,6 +70,20 @@ pub fn main(_: ApplicationConfig) -> Result<(), Box<dyn Error>> {
     app.exit_state
 }
 
+#[cfg(all(any(windows, unix), not(target_vendor = "apple")))]
+fn run_headless(config: HeadlessConfig, scene_file: &str) -> Result<(), Box<dyn Error>> {
+    let mut headless = headless::HeadlessRenderer::new(config.width, config.height, scene_file)?;
+    let image = headless.render();
+    image.save(&config.output)?;
+    info!("saved headless frame to {}", config.output);
+    Ok(())
+}
+
+#[cfg(not(all(any(windows, unix), not(target_vendor = "apple"))))]
+fn run_headless(_: HeadlessConfig, _: &str) -> Result<(), Box<dyn Error>> {
+    Err("headless rendering needs EGL, which isn't available on this platform".into())
+}
+
 struct App {
     gl_display_template: ConfigTemplateBuilder,
     gl_display: GlDisplayCreationState,
@@ -53,10 +91,16 @@ struct App {
     renderer: Option<Renderer>,
     state: Option<AppState>,
     now: Instant,
-    start_time: Instant,
+    screenshot_requested: bool,
+    camera_controller: CameraController,
+    scene_file: String,
     exit_state: Result<(), Box<dyn Error>>,
 }
 
+const SCREENSHOT_DIRECTORY: &str = "screenshots";
+// how far in front of a fly camera the orbit target goes when switching modes
+const ORBIT_DISTANCE: f32 = 3.5;
+
 enum GlDisplayCreationState {
     /// The display was not build yet.
     Builder(DisplayBuilder),
@@ -72,7 +116,7 @@ struct AppState {
 }
 
 impl App {
-    fn new(gl_display_template: ConfigTemplateBuilder, display_builder: DisplayBuilder) -> Self {
+    fn new(gl_display_template: ConfigTemplateBuilder, display_builder: DisplayBuilder, scene_file: String) -> Self {
         Self {
             gl_display_template,
             gl_display: GlDisplayCreationState::Builder(display_builder),
@@ -80,10 +124,44 @@ impl App {
             state: None,
             gl_context: None,
             now: Instant::now(),
-            start_time: Instant::now(),
+            screenshot_requested: false,
+            camera_controller: CameraController::default(),
+            scene_file,
             exit_state: Ok(()),
         }
     }
+
+    /// C switches camera mode, F frames the mesh, P cycles the projection and 1/2/3 give front/side/top views
+    fn view_key_pressed(&mut self, code: KeyCode) {
+        let (Some(AppState { window, .. }), Some(renderer)) = (self.state.as_ref(), self.renderer.as_mut()) else {
+            return;
+        };
+
+        let axis_view = match code {
+            KeyCode::KeyC => {
+                self.camera_controller.set_cursor_grab(window, false);
+                renderer.camera_mut().toggle_mode(ORBIT_DISTANCE);
+                info!("camera mode: {:?}", renderer.camera());
+                None
+            }
+            KeyCode::KeyF => {
+                renderer.frame_object();
+                None
+            }
+            KeyCode::KeyP => {
+                renderer.cycle_projection();
+                None
+            }
+            KeyCode::Digit1 => Some((0.0, 0.0)),
+            KeyCode::Digit2 => Some((-FRAC_PI_2, 0.0)),
+            KeyCode::Digit3 => Some((0.0, -FRAC_PI_2)),
+            _ => None,
+        };
+
+        if let (Some((yaw, pitch)), Camera::Orbit(orbit)) = (axis_view, renderer.camera_mut()) {
+            orbit.align(yaw, pitch);
+        }
+    }
 }
 
 impl ApplicationHandler for App {
@@ -133,7 +211,15 @@ impl ApplicationHandler for App {
         let gl_context = self.gl_context.as_ref().unwrap();
         gl_context.make_current(&gl_surface).unwrap();
 
-        self.renderer.get_or_insert_with(|| Renderer::new(&gl_config.display()).unwrap());
+        if self.renderer.is_none() {
+            let mut renderer = Renderer::new(&gl_config.display()).unwrap();
+            if let Err(e) = SceneDescription::load(&self.scene_file).and_then(|scene| renderer.load_scene(&scene)) {
+                self.exit_state = Err(e.into());
+                event_loop.exit();
+                return;
+            }
+            self.renderer = Some(renderer);
+        }
 
         // Try setting vsync.
         if let Err(res) = gl_surface.set_swap_interval(gl_context, SwapInterval::Wait(NonZeroU32::new(1).unwrap())) {
@@ -165,6 +251,12 @@ impl ApplicationHandler for App {
     }
 
     fn window_event(&mut self, event_loop: &winit::event_loop::ActiveEventLoop, _: winit::window::WindowId, event: winit::event::WindowEvent) {
+        if let (Some(AppState { window, .. }), Some(renderer)) = (self.state.as_ref(), self.renderer.as_ref()) {
+            if self.camera_controller.window_event(window, &event, renderer.camera()) {
+                return;
+            }
+        }
+
         match event {
             WindowEvent::Resized(size) if size.width != 0 && size.height != 0 => {
                 if let Some(AppState { gl_surface, window: _ }) = self.state.as_ref() {
@@ -174,39 +266,89 @@ impl ApplicationHandler for App {
                     let renderer: &mut Renderer = self.renderer.as_mut().unwrap();
                     renderer.resize(size.width as i32, size.height as i32);
 
-                    let delta = self.start_time.elapsed().as_millis() as f32 / 1000.0;
-                    let frame_delta = self.now.elapsed().as_millis() as f32 / 1000.0;
-                    self.now = Instant::now();
-
-                    trace!("delta: {}, frame_delta {},", delta, frame_delta);
-
-                    renderer.draw(delta, frame_delta);
+                    renderer.draw();
                 }
             }
-            WindowEvent::CloseRequested
-            | WindowEvent::KeyboardInput {
+            WindowEvent::KeyboardInput {
                 event: KeyEvent {
                     logical_key: Key::Named(NamedKey::Escape),
+                    state,
+                    repeat,
                     ..
                 },
                 ..
-            } => event_loop.exit(),
+            } => match self.camera_controller.escape(state, repeat) {
+                EscapeAction::ReleaseCursor => {
+                    if let Some(AppState { window, .. }) = self.state.as_ref() {
+                        self.camera_controller.set_cursor_grab(window, false);
+                    }
+                }
+                EscapeAction::Exit => event_loop.exit(),
+                EscapeAction::Ignore => (),
+            },
+            WindowEvent::CloseRequested => event_loop.exit(),
+            // taken in `about_to_wait` between drawing and swapping, while the back buffer holds the frame
+            WindowEvent::KeyboardInput {
+                event: KeyEvent {
+                    logical_key: Key::Named(NamedKey::F12),
+                    state: ElementState::Pressed,
+                    repeat: false,
+                    ..
+                },
+                ..
+            } => self.screenshot_requested = true,
+            WindowEvent::KeyboardInput {
+                event: KeyEvent {
+                    logical_key: Key::Named(NamedKey::F5),
+                    state: ElementState::Pressed,
+                    repeat: false,
+                    ..
+                },
+                ..
+            } => {
+                if let Some(renderer) = self.renderer.as_ref() {
+                    if let Err(e) = renderer.scene_description().save(&self.scene_file) {
+                        error!("failed to save scene: {:?}", e);
+                    }
+                }
+            }
+            WindowEvent::KeyboardInput {
+                event: KeyEvent {
+                    physical_key: PhysicalKey::Code(code),
+                    state: ElementState::Pressed,
+                    repeat: false,
+                    ..
+                },
+                ..
+            } => self.view_key_pressed(code),
             _ => (),
         }
     }
 
+    fn device_event(&mut self, _event_loop: &ActiveEventLoop, _device_id: DeviceId, event: DeviceEvent) {
+        self.camera_controller.device_event(&event);
+    }
+
     fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
         if let Some(AppState { gl_surface, window }) = self.state.as_ref() {
             let gl_context = self.gl_context.as_ref().unwrap();
             let renderer = self.renderer.as_mut().unwrap();
 
-            let delta = self.start_time.elapsed().as_millis() as f32 / 1000.0;
             let frame_delta = self.now.elapsed().as_millis() as f32 / 1000.0;
             self.now = Instant::now();
 
-            trace!("delta: {}, frame_delta {},", delta, frame_delta);
+            trace!("frame_delta {}", frame_delta);
+
+            renderer.reload_changed_shaders();
+            self.camera_controller.update(renderer.camera_mut(), frame_delta);
+            renderer.draw();
+
+            if std::mem::take(&mut self.screenshot_requested) {
+                if let Err(e) = renderer.save_screenshot(SCREENSHOT_DIRECTORY) {
+                    error!("failed to take screenshot: {:?}", e);
+                }
+            }
 
-            renderer.draw(delta, frame_delta);
             window.request_redraw();
 
             gl_surface.swap_buffers(gl_context).unwrap();
@@ -0,0 +1,180 @@
+use anyhow::{anyhow, Result};
+use gl::types::*;
+use log::info;
+use std::ffi::c_void;
+use std::fmt;
+use std::marker::PhantomData;
+
+use super::gl;
+
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BufferTarget {
+    VERTEX = gl::ARRAY_BUFFER as isize,
+    INDEX = gl::ELEMENT_ARRAY_BUFFER as isize,
+    UNIFORM = gl::UNIFORM_BUFFER as isize,
+    STORAGE = gl::SHADER_STORAGE_BUFFER as isize,
+    INDIRECT = gl::DRAW_INDIRECT_BUFFER as isize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BufferStorage {
+    /// written once at creation, can't be updated afterwards
+    IMMUTABLE,
+    /// updated with glNamedBufferSubData
+    DYNAMIC,
+    /// mapped for the lifetime of the buffer, writes are coherent so no flushing is needed
+    PERSISTENT,
+}
+
+impl BufferStorage {
+    fn flags(self) -> GLbitfield {
+        match self {
+            BufferStorage::IMMUTABLE => 0,
+            BufferStorage::DYNAMIC => gl::DYNAMIC_STORAGE_BIT,
+            BufferStorage::PERSISTENT => gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT,
+        }
+    }
+}
+
+/// GL buffer holding `len` values of `T`. T is copied to the GPU byte for byte so must be `#[repr(C)]`
+/// and laid out to match the shader side.
+pub struct Buffer<T: Copy> {
+    pub handle: u32,
+    pub target: BufferTarget,
+    pub storage: BufferStorage,
+    len: usize,
+    mapped: *mut T,
+    buffer_type: PhantomData<T>,
+}
+
+impl<T: Copy> Buffer<T> {
+    pub fn new(target: BufferTarget, storage: BufferStorage, data: &[T]) -> Result<Self> {
+        Buffer::create(target, storage, data.len(), data.as_ptr() as *const c_void)
+    }
+
+    /// buffer of `len` elements with undefined contents, only useful for storage that can be written later
+    pub fn with_len(target: BufferTarget, storage: BufferStorage, len: usize) -> Result<Self> {
+        if storage == BufferStorage::IMMUTABLE {
+            return Err(anyhow!("immutable {:?} buffer must be created with its data", target));
+        }
+        Buffer::create(target, storage, len, std::ptr::null())
+    }
+
+    fn create(target: BufferTarget, storage: BufferStorage, len: usize, data: *const c_void) -> Result<Self> {
+        let memory_size = (len * size_of::<T>()) as isize;
+
+        let (handle, mapped) = unsafe {
+            let mut buffer: GLuint = 0;
+            gl::CreateBuffers(1, &mut buffer);
+            // zero sized storage is an error, keep at least one byte so empty meshes stay valid
+            match memory_size {
+                0 => gl::NamedBufferStorage(buffer, 1, std::ptr::null(), storage.flags()),
+                _ => gl::NamedBufferStorage(buffer, memory_size, data, storage.flags()),
+            }
+
+            let mapped = match storage {
+                BufferStorage::PERSISTENT => gl::MapNamedBufferRange(buffer, 0, memory_size, storage.flags()) as *mut T,
+                _ => std::ptr::null_mut(),
+            };
+            (buffer, mapped)
+        };
+
+        if storage == BufferStorage::PERSISTENT && mapped.is_null() {
+            unsafe { gl::DeleteBuffers(1, &handle) };
+            return Err(anyhow!("glMapNamedBufferRange failed for {:?} buffer #{}", target, handle));
+        }
+
+        info!("created {:?} {:?} buffer #{} with memory size {}", storage, target, handle, memory_size);
+
+        return Ok(Buffer::<T> {
+            handle,
+            target,
+            storage,
+            len,
+            mapped,
+            buffer_type: PhantomData::<T>,
+        });
+    }
+
+    pub fn memory_size(&self) -> isize {
+        (self.len * size_of::<T>()) as isize
+    }
+
+    /// uniform and storage buffers are bound to `binding`, other targets ignore it and bind the target directly
+    pub unsafe fn bind(&self, binding: u32) {
+        info!("binding {} to {}", self, binding);
+        match self.target {
+            BufferTarget::UNIFORM | BufferTarget::STORAGE => gl::BindBufferRange(self.target as u32, binding, self.handle, 0, self.memory_size()),
+            _ => gl::BindBuffer(self.target as u32, self.handle),
+        }
+    }
+
+    /// overwrites elements starting at `offset`
+    pub fn sub_data(&mut self, offset: usize, data: &[T]) -> Result<()> {
+        if offset + data.len() > self.len {
+            return Err(anyhow!("write of {} elements at {} overruns {} of length {}", data.len(), offset, self, self.len));
+        }
+
+        match self.storage {
+            BufferStorage::IMMUTABLE => return Err(anyhow!("{} is immutable", self)),
+            BufferStorage::DYNAMIC => unsafe {
+                gl::NamedBufferSubData(
+                    self.handle,
+                    (offset * size_of::<T>()) as isize,
+                    std::mem::size_of_val(data) as isize,
+                    data.as_ptr() as *const c_void,
+                );
+            },
+            BufferStorage::PERSISTENT => self.mapped_mut().unwrap()[offset..offset + data.len()].copy_from_slice(data),
+        }
+
+        Ok(())
+    }
+
+    /// copies the contents back from the GPU, waiting for anything still writing to the buffer.
+    /// shader writes need a `memory_barrier` with `Barrier::BUFFER_UPDATE` first
+    #[allow(unused)]
+    pub fn read(&self) -> Vec<T> {
+        let mut data: Vec<T> = Vec::with_capacity(self.len);
+        unsafe {
+            gl::GetNamedBufferSubData(self.handle, 0, self.memory_size(), data.as_mut_ptr() as *mut c_void);
+            data.set_len(self.len);
+        }
+        data
+    }
+
+    /// the persistent mapping, `None` for other storage types.
+    /// the caller must sync with the GPU (e.g. a fence) before overwriting data a draw may still be reading
+    pub fn mapped_mut(&mut self) -> Option<&mut [T]> {
+        match self.mapped.is_null() {
+            true => None,
+            false => Some(unsafe { std::slice::from_raw_parts_mut(self.mapped, self.len) }),
+        }
+    }
+}
+
+impl<T: Copy> Drop for Buffer<T> {
+    fn drop(&mut self) {
+        info!("deleting {}", self);
+        unsafe {
+            if !self.mapped.is_null() {
+                gl::UnmapNamedBuffer(self.handle);
+            }
+            gl::DeleteBuffers(1, &self.handle);
+        }
+    }
+}
+
+impl<T: Copy> fmt::Display for Buffer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let target = match self.target {
+            BufferTarget::VERTEX => "vertex",
+            BufferTarget::INDEX => "index",
+            BufferTarget::UNIFORM => "uniform",
+            BufferTarget::STORAGE => "storage",
+            BufferTarget::INDIRECT => "indirect",
+        };
+        write!(f, "{} buffer #{}", target, self.handle)
+    }
+}
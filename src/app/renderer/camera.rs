@@ -0,0 +1,284 @@
+use std::f32::consts::FRAC_PI_2;
+
+use super::intrinsics::Radians;
+use super::mesh::Bounds;
+
+/// yaw 0 and pitch 0 look down -z with +y up, angles are in radians.
+/// both modes share that convention so switching between them keeps the view where it is.
+#[derive(Clone, Debug)]
+pub enum Camera {
+    Fly(FlyCamera),
+    Orbit(OrbitCamera),
+}
+
+/// first person camera moved with the keyboard
+#[derive(Clone, Debug)]
+pub struct FlyCamera {
+    pub position: glm::Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    /// units per second
+    pub speed: f32,
+    /// radians per pixel of mouse movement
+    pub sensitivity: f32,
+}
+
+/// model viewer camera that circles `target` at `distance`
+#[derive(Clone, Debug)]
+pub struct OrbitCamera {
+    pub target: glm::Vec3,
+    pub distance: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    /// radians per pixel of mouse movement
+    pub sensitivity: f32,
+}
+
+// stop just short of straight up/down, where the view matrix would flip
+const PITCH_LIMIT: f32 = FRAC_PI_2 - 0.001;
+const MIN_ORBIT_DISTANCE: f32 = 0.01;
+
+fn forward(yaw: f32, pitch: f32) -> glm::Vec3 {
+    glm::vec3(pitch.cos() * yaw.sin(), pitch.sin(), -pitch.cos() * yaw.cos())
+}
+
+fn right(yaw: f32, pitch: f32) -> glm::Vec3 {
+    glm::normalize(&glm::cross(&forward(yaw, pitch), &glm::Vec3::y()))
+}
+
+impl Camera {
+    pub fn view_matrix(&self) -> glm::Mat4 {
+        match self {
+            Camera::Fly(fly) => fly.view_matrix(),
+            Camera::Orbit(orbit) => orbit.view_matrix(),
+        }
+    }
+
+    /// switches between fly and orbit, keeping the current view.
+    /// a fly camera orbits a point `distance` in front of it
+    pub fn toggle_mode(&mut self, distance: f32) {
+        *self = match self {
+            Camera::Fly(fly) => Camera::Orbit(OrbitCamera {
+                target: fly.position + fly.forward() * distance,
+                distance,
+                yaw: fly.yaw,
+                pitch: fly.pitch,
+                sensitivity: fly.sensitivity * 2.5,
+            }),
+            Camera::Orbit(orbit) => Camera::Fly(FlyCamera {
+                position: orbit.position(),
+                yaw: orbit.yaw,
+                pitch: orbit.pitch,
+                sensitivity: orbit.sensitivity / 2.5,
+                ..FlyCamera::new(orbit.position())
+            }),
+        }
+    }
+
+    /// moves the camera back along its view direction until `bounds` fits in `vertical_fov`
+    pub fn frame(&mut self, bounds: &Bounds, vertical_fov: Radians) {
+        match self {
+            Camera::Orbit(orbit) => orbit.frame(bounds, vertical_fov),
+            Camera::Fly(fly) => {
+                let mut orbit = OrbitCamera::new(bounds.centre(), 1.0);
+                orbit.yaw = fly.yaw;
+                orbit.pitch = fly.pitch;
+                orbit.frame(bounds, vertical_fov);
+                fly.position = orbit.position();
+            }
+        }
+    }
+}
+
+impl FlyCamera {
+    pub fn new(position: glm::Vec3) -> Self {
+        FlyCamera {
+            position,
+            yaw: 0.0,
+            pitch: 0.0,
+            speed: 2.5,
+            sensitivity: 0.002,
+        }
+    }
+
+    pub fn forward(&self) -> glm::Vec3 {
+        forward(self.yaw, self.pitch)
+    }
+
+    pub fn right(&self) -> glm::Vec3 {
+        right(self.yaw, self.pitch)
+    }
+
+    pub fn view_matrix(&self) -> glm::Mat4 {
+        glm::look_at(&self.position, &(self.position + self.forward()), &glm::Vec3::y())
+    }
+
+    /// `direction` is (right, up, forward) and is normalised, so diagonal movement isn't faster.
+    /// up is always world up so flying doesn't depend on where the camera looks
+    pub fn fly(&mut self, direction: glm::Vec3, frame_delta: f32) {
+        if direction == glm::Vec3::zeros() {
+            return;
+        }
+        let direction = glm::normalize(&direction);
+        let movement = self.right() * direction.x + glm::Vec3::y() * direction.y + self.forward() * direction.z;
+        self.position += movement * self.speed * frame_delta;
+    }
+
+    /// mouse movement in pixels, +x looks right and +y looks down
+    pub fn look(&mut self, delta_x: f32, delta_y: f32) {
+        self.yaw += delta_x * self.sensitivity;
+        self.pitch = (self.pitch - delta_y * self.sensitivity).clamp(-PITCH_LIMIT, PITCH_LIMIT);
+    }
+}
+
+impl OrbitCamera {
+    pub fn new(target: glm::Vec3, distance: f32) -> Self {
+        OrbitCamera {
+            target,
+            distance,
+            yaw: 0.0,
+            pitch: 0.0,
+            sensitivity: 0.005,
+        }
+    }
+
+    pub fn position(&self) -> glm::Vec3 {
+        self.target - forward(self.yaw, self.pitch) * self.distance
+    }
+
+    pub fn view_matrix(&self) -> glm::Mat4 {
+        glm::look_at(&self.position(), &self.target, &glm::Vec3::y())
+    }
+
+    /// mouse drag in pixels, the object follows the cursor
+    pub fn rotate(&mut self, delta_x: f32, delta_y: f32) {
+        self.yaw += delta_x * self.sensitivity;
+        self.pitch = (self.pitch - delta_y * self.sensitivity).clamp(-PITCH_LIMIT, PITCH_LIMIT);
+    }
+
+    /// looks at the target from a fixed direction, e.g. straight down for a top view
+    pub fn align(&mut self, yaw: f32, pitch: f32) {
+        self.yaw = yaw;
+        self.pitch = pitch.clamp(-PITCH_LIMIT, PITCH_LIMIT);
+    }
+
+    /// positive steps (scrolling up) move closer, each step covers 10% of the remaining distance
+    pub fn zoom(&mut self, steps: f32) {
+        self.distance = (self.distance * 0.9_f32.powf(steps)).max(MIN_ORBIT_DISTANCE);
+    }
+
+    /// mouse drag in pixels, scaled by distance so the target keeps pace with the cursor
+    pub fn pan(&mut self, delta_x: f32, delta_y: f32) {
+        let right = right(self.yaw, self.pitch);
+        let up = glm::cross(&right, &forward(self.yaw, self.pitch));
+        let scale = self.distance * 0.001;
+        self.target += (-right * delta_x + up * delta_y) * scale;
+    }
+
+    pub fn frame(&mut self, bounds: &Bounds, vertical_fov: Radians) {
+        self.target = bounds.centre();
+        self.distance = (bounds.radius() / (vertical_fov.0 / 2.0).sin()).max(MIN_ORBIT_DISTANCE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: &glm::Vec3, b: &glm::Vec3) {
+        assert!(glm::distance(a, b) < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn default_view_looks_down_negative_z() {
+        let camera = FlyCamera::new(glm::vec3(0.0, 0.0, 3.5));
+
+        let expected = glm::translate(&glm::Mat4::identity(), &glm::vec3(0.0, 0.0, -3.5));
+        assert!(glm::abs(&(camera.view_matrix() - expected)).max() < 1e-6);
+        assert_near(&camera.right(), &glm::vec3(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn movement_scales_with_frame_delta() {
+        let mut camera = FlyCamera::new(glm::Vec3::zeros());
+        camera.speed = 2.0;
+
+        camera.fly(glm::vec3(0.0, 0.0, 1.0), 0.5);
+        assert_near(&camera.position, &glm::vec3(0.0, 0.0, -1.0));
+
+        // diagonals are normalised
+        camera.fly(glm::vec3(1.0, 0.0, 1.0), 1.0);
+        let step = 2.0 / 2.0_f32.sqrt();
+        assert_near(&camera.position, &glm::vec3(step, 0.0, -1.0 - step));
+    }
+
+    #[test]
+    fn looking_turns_and_clamps_pitch() {
+        let mut camera = FlyCamera::new(glm::Vec3::zeros());
+        camera.sensitivity = 0.01;
+
+        camera.look(FRAC_PI_2 * 100.0, 0.0);
+        assert_near(&camera.forward(), &glm::vec3(1.0, 0.0, 0.0));
+
+        camera.look(0.0, -10_000.0);
+        assert_eq!(camera.pitch, PITCH_LIMIT);
+    }
+
+    #[test]
+    fn orbit_circles_target() {
+        let mut orbit = OrbitCamera::new(glm::vec3(1.0, 0.0, 0.0), 2.0);
+        assert_near(&orbit.position(), &glm::vec3(1.0, 0.0, 2.0));
+
+        // dragging right brings the camera round to the -x side
+        orbit.sensitivity = 0.01;
+        orbit.rotate(FRAC_PI_2 * 100.0, 0.0);
+        assert_near(&orbit.position(), &glm::vec3(-1.0, 0.0, 0.0));
+
+        orbit.zoom(1.0);
+        assert!((orbit.distance - 1.8).abs() < 1e-6);
+        orbit.zoom(-1.0);
+        assert!((orbit.distance - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn pan_moves_target_in_view_plane() {
+        let mut orbit = OrbitCamera::new(glm::Vec3::zeros(), 1000.0);
+        orbit.pan(1.0, 1.0);
+
+        // dragging right and down moves the scene with the cursor, so the target goes left and up
+        assert_near(&orbit.target, &glm::vec3(-1.0, 1.0, 0.0));
+        assert_eq!(orbit.distance, 1000.0);
+    }
+
+    #[test]
+    fn frame_fits_bounds() {
+        let bounds = Bounds {
+            min: glm::vec3(-1.0, -1.0, -1.0),
+            max: glm::vec3(3.0, 1.0, 1.0),
+        };
+        let mut camera = Camera::Orbit(OrbitCamera::new(glm::Vec3::zeros(), 1.0));
+        camera.frame(&bounds, Radians(FRAC_PI_2));
+
+        let Camera::Orbit(orbit) = &camera else { unreachable!() };
+        assert_near(&orbit.target, &glm::vec3(1.0, 0.0, 0.0));
+        // radius sqrt(6) over sin(45 degrees)
+        assert!((orbit.distance - 6.0_f32.sqrt() * 2.0_f32.sqrt()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn toggling_mode_keeps_the_view() {
+        let mut fly = FlyCamera::new(glm::vec3(1.0, 2.0, 3.0));
+        fly.yaw = 0.4;
+        fly.pitch = -0.2;
+        let mut camera = Camera::Fly(fly);
+        let view = camera.view_matrix();
+
+        camera.toggle_mode(5.0);
+        assert!(matches!(camera, Camera::Orbit(_)));
+        assert!(glm::abs(&(camera.view_matrix() - view)).max() < 1e-5);
+
+        camera.toggle_mode(5.0);
+        assert!(matches!(camera, Camera::Fly(_)));
+        assert!(glm::abs(&(camera.view_matrix() - view)).max() < 1e-5);
+    }
+}
@@ -0,0 +1,125 @@
+use anyhow::{anyhow, Context, Result};
+use gl::types::*;
+use log::info;
+use std::fmt;
+
+use super::gl;
+use super::program::Program;
+use super::shader::{Shader, ShaderType};
+
+/// what a `memory_barrier` makes visible: writes from earlier shaders as seen by the later use named.
+/// a GLbitfield rather than an enum as `ALL` doesn't fit in a portable enum discriminant
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Barrier(pub GLbitfield);
+
+#[allow(unused)]
+impl Barrier {
+    pub const VERTEX_ATTRIBUTE: Barrier = Barrier(gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT);
+    pub const INDEX: Barrier = Barrier(gl::ELEMENT_ARRAY_BARRIER_BIT);
+    pub const UNIFORM: Barrier = Barrier(gl::UNIFORM_BARRIER_BIT);
+    pub const TEXTURE_FETCH: Barrier = Barrier(gl::TEXTURE_FETCH_BARRIER_BIT);
+    pub const IMAGE_ACCESS: Barrier = Barrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+    pub const COMMAND: Barrier = Barrier(gl::COMMAND_BARRIER_BIT);
+    pub const BUFFER_UPDATE: Barrier = Barrier(gl::BUFFER_UPDATE_BARRIER_BIT);
+    pub const FRAMEBUFFER: Barrier = Barrier(gl::FRAMEBUFFER_BARRIER_BIT);
+    pub const SHADER_STORAGE: Barrier = Barrier(gl::SHADER_STORAGE_BARRIER_BIT);
+    pub const ALL: Barrier = Barrier(gl::ALL_BARRIER_BITS);
+}
+
+/// waits for incoherent writes by earlier draws and dispatches (storage buffers, images, atomics)
+/// before they're read through any of `barriers`
+#[allow(unused)]
+pub unsafe fn memory_barrier(barriers: &[Barrier]) {
+    let bits = barriers.iter().fold(0, |bits, barrier| bits | barrier.0);
+    gl::MemoryBarrier(bits);
+}
+
+/// a program with a single compute stage, run with `dispatch` instead of a draw call
+#[allow(unused)]
+pub struct ComputeProgram {
+    pub program: Program,
+    /// `local_size_x/y/z` declared by the shader
+    pub work_group_size: [u32; 3],
+    max_work_group_count: [u32; 3],
+}
+
+#[allow(unused)]
+impl ComputeProgram {
+    pub fn new(compute_shader: &Shader) -> Result<Self> {
+        if compute_shader.shader_type != ShaderType::COMPUTE {
+            return Err(anyhow!("{} is not a compute shader", compute_shader));
+        }
+        let program = Program::from_stages(&[compute_shader])?;
+
+        let mut work_group_size = [0; 3];
+        let mut max_work_group_count = [0; 3];
+        unsafe {
+            gl::GetProgramiv(program.handle, gl::COMPUTE_WORK_GROUP_SIZE, work_group_size.as_mut_ptr());
+            for (index, count) in max_work_group_count.iter_mut().enumerate() {
+                gl::GetIntegeri_v(gl::MAX_COMPUTE_WORK_GROUP_COUNT, index as GLuint, count);
+            }
+        }
+
+        info!("{} runs work groups of {:?}", program, work_group_size);
+        return Ok(ComputeProgram {
+            program,
+            work_group_size: work_group_size.map(|size| size as u32),
+            max_work_group_count: max_work_group_count.map(|count| count as u32),
+        });
+    }
+
+    /// runs `x * y * z` work groups. the results can only be relied on after a `memory_barrier`
+    /// for the way they'll be read
+    pub unsafe fn dispatch(&self, x: u32, y: u32, z: u32) -> Result<()> {
+        let counts = [x, y, z];
+        if counts.iter().zip(self.max_work_group_count).any(|(&count, max)| count > max) {
+            return Err(anyhow!("dispatch of {:?} work groups exceeds the limit of {:?}", counts, self.max_work_group_count))
+                .with_context(|| format!("can't dispatch {}", self));
+        }
+
+        self.program.use_program();
+        gl::DispatchCompute(x, y, z);
+        Ok(())
+    }
+
+    /// enough work groups to cover `invocations` in each dimension, rounding up
+    pub fn work_groups_for(&self, invocations: [u32; 3]) -> [u32; 3] {
+        [0, 1, 2].map(|axis| invocations[axis].div_ceil(self.work_group_size[axis].max(1)))
+    }
+}
+
+impl fmt::Display for ComputeProgram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "compute {}", self.program)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::headless::gl_test_context;
+    use crate::app::renderer::buffer::{Buffer, BufferStorage, BufferTarget};
+    use crate::app::renderer::shader::stage_fixture;
+
+    #[test]
+    fn compute_program_fills_storage_buffer() {
+        let _context = gl_test_context();
+
+        let compute = ComputeProgram::new(&stage_fixture(ShaderType::COMPUTE, "compute")).unwrap();
+        assert_eq!(compute.work_group_size, [8, 1, 1]);
+        assert!(ComputeProgram::new(&stage_fixture(ShaderType::VERTEX, "vertex")).is_err());
+
+        let buffer = Buffer::<u32>::with_len(BufferTarget::STORAGE, BufferStorage::DYNAMIC, 20).unwrap();
+        let [x, y, z] = compute.work_groups_for([20, 1, 1]);
+        assert_eq!([x, y, z], [3, 1, 1]);
+        unsafe {
+            buffer.bind(0);
+            compute.dispatch(x, y, z).unwrap();
+            memory_barrier(&[Barrier::BUFFER_UPDATE]);
+        }
+
+        let expected: Vec<u32> = (0..20).map(|i| i * i).collect();
+        assert_eq!(buffer.read(), expected);
+        assert!(unsafe { compute.dispatch(u32::MAX, 1, 1) }.is_err());
+    }
+}
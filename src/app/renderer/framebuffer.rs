@@ -0,0 +1,293 @@
+use anyhow::{anyhow, Context, Result};
+use gl::types::*;
+use log::info;
+use std::fmt;
+
+use super::gl;
+use super::texture::Texture;
+
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorFormat {
+    RGBA8 = gl::RGBA8 as isize,
+    RGBA16F = gl::RGBA16F as isize,
+    R32F = gl::R32F as isize,
+}
+
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DepthFormat {
+    DEPTH24 = gl::DEPTH_COMPONENT24 as isize,
+    DEPTH32F = gl::DEPTH_COMPONENT32F as isize,
+    DEPTH24STENCIL8 = gl::DEPTH24_STENCIL8 as isize,
+}
+
+impl DepthFormat {
+    fn attachment(self) -> GLenum {
+        match self {
+            DepthFormat::DEPTH24STENCIL8 => gl::DEPTH_STENCIL_ATTACHMENT,
+            _ => gl::DEPTH_ATTACHMENT,
+        }
+    }
+}
+
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DepthAttachment {
+    /// write only, cheapest when depth is only needed for testing
+    Renderbuffer(DepthFormat),
+    /// can be sampled afterwards, e.g. for shadow maps
+    Texture(DepthFormat),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FramebufferDescriptor {
+    /// attached to COLOR_ATTACHMENT0.. in order
+    pub color: Vec<ColorFormat>,
+    pub depth: Option<DepthAttachment>,
+}
+
+enum DepthStorage {
+    Renderbuffer(Renderbuffer),
+    Texture(Texture),
+}
+
+pub struct Framebuffer {
+    pub handle: u32,
+    pub width: i32,
+    pub height: i32,
+    descriptor: FramebufferDescriptor,
+    color_attachments: Vec<Texture>,
+    depth_attachment: Option<DepthStorage>,
+}
+
+impl Framebuffer {
+    pub fn new(descriptor: FramebufferDescriptor, width: i32, height: i32) -> Result<Self> {
+        let (framebuffer_id, color_attachments, depth_attachment) = create_attachments(&descriptor, width, height)?;
+
+        let framebuffer = Framebuffer {
+            handle: framebuffer_id,
+            width,
+            height,
+            descriptor,
+            color_attachments,
+            depth_attachment,
+        };
+
+        info!("created {} ({}x{}, {:?})", framebuffer, width, height, framebuffer.descriptor);
+        return Ok(framebuffer);
+    }
+
+    /// recreates every attachment at the new size, their previous contents are lost.
+    /// the new attachments are put together on a new framebuffer object and only replace the old one once
+    /// it's complete, so if anything fails the framebuffer keeps its old size and attachments
+    pub fn resize(&mut self, width: i32, height: i32) -> Result<()> {
+        if (width, height) == (self.width, self.height) {
+            return Ok(());
+        }
+        info!("resizing {} to {}x{}", self, width, height);
+        let (framebuffer_id, color_attachments, depth_attachment) = create_attachments(&self.descriptor, width, height).with_context(|| format!("failed to resize {}", self))?;
+
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.handle);
+        }
+        self.handle = framebuffer_id;
+        self.width = width;
+        self.height = height;
+        self.color_attachments = color_attachments;
+        self.depth_attachment = depth_attachment;
+        Ok(())
+    }
+
+    pub unsafe fn bind(&self) {
+        gl::BindFramebuffer(gl::FRAMEBUFFER, self.handle);
+    }
+
+    #[allow(unused)]
+    pub fn color_attachment(&self, index: usize) -> Option<&Texture> {
+        self.color_attachments.get(index)
+    }
+
+    #[allow(unused)]
+    pub fn depth_texture(&self) -> Option<&Texture> {
+        match &self.depth_attachment {
+            Some(DepthStorage::Texture(texture)) => Some(texture),
+            _ => None,
+        }
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        info!("deleting {}", self);
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.handle);
+        }
+    }
+}
+
+impl fmt::Display for Framebuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "framebuffer #{}", self.handle)
+    }
+}
+
+/// a new framebuffer object with `descriptor`'s attachments at `width`x`height`, deleted again unless it's complete
+fn create_attachments(descriptor: &FramebufferDescriptor, width: i32, height: i32) -> Result<(GLuint, Vec<Texture>, Option<DepthStorage>)> {
+    if width <= 0 || height <= 0 {
+        return Err(anyhow!("framebuffer can't be {}x{}", width, height));
+    }
+
+    let color_attachments = descriptor
+        .color
+        .iter()
+        .map(|&format| Texture::with_storage(width, height, format as GLenum))
+        .collect::<Result<Vec<_>>>()?;
+    let depth_attachment = match descriptor.depth {
+        Some(DepthAttachment::Renderbuffer(format)) => Some(DepthStorage::Renderbuffer(Renderbuffer::new(format, width, height))),
+        Some(DepthAttachment::Texture(format)) => Some(DepthStorage::Texture(Texture::with_storage(width, height, format as GLenum)?)),
+        None => None,
+    };
+
+    let framebuffer_id = unsafe {
+        let mut fbo: GLuint = 0;
+        gl::CreateFramebuffers(1, &mut fbo);
+
+        let draw_buffers: Vec<GLenum> = (0..color_attachments.len() as u32).map(|i| gl::COLOR_ATTACHMENT0 + i).collect();
+        for (&attachment, texture) in draw_buffers.iter().zip(&color_attachments) {
+            gl::NamedFramebufferTexture(fbo, attachment, texture.handle, 0);
+        }
+
+        // a depth only framebuffer has nothing to draw or read colour from
+        if draw_buffers.is_empty() {
+            gl::NamedFramebufferDrawBuffer(fbo, gl::NONE);
+            gl::NamedFramebufferReadBuffer(fbo, gl::NONE);
+        } else {
+            gl::NamedFramebufferDrawBuffers(fbo, draw_buffers.len() as GLsizei, draw_buffers.as_ptr());
+            gl::NamedFramebufferReadBuffer(fbo, gl::COLOR_ATTACHMENT0);
+        }
+
+        match (&depth_attachment, descriptor.depth) {
+            (Some(DepthStorage::Renderbuffer(renderbuffer)), Some(DepthAttachment::Renderbuffer(format))) => {
+                gl::NamedFramebufferRenderbuffer(fbo, format.attachment(), gl::RENDERBUFFER, renderbuffer.handle)
+            }
+            (Some(DepthStorage::Texture(texture)), Some(DepthAttachment::Texture(format))) => gl::NamedFramebufferTexture(fbo, format.attachment(), texture.handle, 0),
+            _ => (),
+        }
+        fbo
+    };
+
+    if let Err(e) = check_complete(framebuffer_id, descriptor) {
+        unsafe { gl::DeleteFramebuffers(1, &framebuffer_id) };
+        return Err(e);
+    }
+    Ok((framebuffer_id, color_attachments, depth_attachment))
+}
+
+fn check_complete(framebuffer_id: GLuint, descriptor: &FramebufferDescriptor) -> Result<()> {
+    let status = unsafe { gl::CheckNamedFramebufferStatus(framebuffer_id, gl::FRAMEBUFFER) };
+
+    let reason = match status {
+        gl::FRAMEBUFFER_COMPLETE => return Ok(()),
+        gl::FRAMEBUFFER_UNDEFINED => "GL_FRAMEBUFFER_UNDEFINED: the default framebuffer does not exist",
+        gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "GL_FRAMEBUFFER_INCOMPLETE_ATTACHMENT: an attachment is incomplete or has a zero size",
+        gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => "GL_FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT: no images are attached",
+        gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => "GL_FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER: a draw buffer names an attachment with no image",
+        gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => "GL_FRAMEBUFFER_INCOMPLETE_READ_BUFFER: the read buffer names an attachment with no image",
+        gl::FRAMEBUFFER_UNSUPPORTED => "GL_FRAMEBUFFER_UNSUPPORTED: the driver does not support this combination of formats",
+        gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => "GL_FRAMEBUFFER_INCOMPLETE_MULTISAMPLE: attachments have different sample counts",
+        gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => "GL_FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS: layered and non layered attachments are mixed",
+        _ => "unknown framebuffer status",
+    };
+
+    Err(anyhow!("framebuffer #{} is incomplete ({:#x}) {}: {:?}", framebuffer_id, status, reason, descriptor))
+}
+
+struct Renderbuffer {
+    handle: u32,
+}
+
+impl Renderbuffer {
+    fn new(format: DepthFormat, width: i32, height: i32) -> Self {
+        let renderbuffer_id = unsafe {
+            let mut rbo: GLuint = 0;
+            gl::CreateRenderbuffers(1, &mut rbo);
+            gl::NamedRenderbufferStorage(rbo, format as GLenum, width, height);
+            rbo
+        };
+        Renderbuffer { handle: renderbuffer_id }
+    }
+}
+
+impl Drop for Renderbuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteRenderbuffers(1, &self.handle);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::headless::gl_test_context;
+
+    #[test]
+    fn framebuffer_supports_float_and_depth_texture_attachments() {
+        let _context = gl_test_context();
+
+        let descriptor = FramebufferDescriptor {
+            color: vec![ColorFormat::RGBA16F, ColorFormat::R32F],
+            depth: Some(DepthAttachment::Texture(DepthFormat::DEPTH32F)),
+        };
+        let framebuffer = Framebuffer::new(descriptor.clone(), 16, 16).unwrap();
+        assert!(framebuffer.color_attachment(1).is_some());
+        assert!(framebuffer.depth_texture().is_some());
+
+        let error = Framebuffer::new(descriptor.clone(), 0, 16).err().unwrap();
+        assert!(error.to_string().contains("can't be 0x16"), "{}", error);
+
+        // a failed resize leaves the framebuffer as it was
+        let mut framebuffer = framebuffer;
+        assert!(framebuffer.resize(-1, 16).is_err());
+        assert_eq!((framebuffer.width, framebuffer.height), (16, 16));
+        assert!(framebuffer.color_attachment(1).is_some());
+
+        let nothing_attached = FramebufferDescriptor {
+            color: Vec::new(),
+            depth: None,
+        };
+        let error = Framebuffer::new(nothing_attached, 16, 16).err().unwrap();
+        assert!(error.to_string().contains("GL_FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT"), "{}", error);
+    }
+
+    #[test]
+    fn failed_resize_keeps_the_old_attachments_usable() {
+        let _context = gl_test_context();
+        let descriptor = FramebufferDescriptor {
+            color: vec![ColorFormat::RGBA8],
+            depth: Some(DepthAttachment::Renderbuffer(DepthFormat::DEPTH24)),
+        };
+        let mut framebuffer = Framebuffer::new(descriptor, 16, 16).unwrap();
+        let handle = framebuffer.handle;
+
+        // texture storage past the size limit fails, leaving the new colour attachment incomplete
+        let mut max_size = 0;
+        unsafe { gl::GetIntegerv(gl::MAX_TEXTURE_SIZE, &mut max_size) };
+        let error = framebuffer.resize(max_size + 1, 16).err().unwrap();
+        assert!(format!("{:#}", error).contains("GL_FRAMEBUFFER_INCOMPLETE_ATTACHMENT"), "{:#}", error);
+
+        assert_eq!((framebuffer.handle, framebuffer.width, framebuffer.height), (handle, 16, 16));
+        check_complete(framebuffer.handle, &framebuffer.descriptor).unwrap();
+        let mut pixel = [0u8; 4];
+        unsafe {
+            gl::ClearNamedFramebufferfv(framebuffer.handle, gl::COLOR, 0, [1.0f32, 0.0, 0.0, 1.0].as_ptr());
+            gl::GetTextureSubImage(framebuffer.color_attachment(0).unwrap().handle, 0, 15, 15, 0, 1, 1, 1, gl::RGBA, gl::UNSIGNED_BYTE, 4, pixel.as_mut_ptr() as *mut _);
+        }
+        assert_eq!(pixel, [255, 0, 0, 255]);
+
+        framebuffer.resize(32, 8).unwrap();
+        assert_eq!((framebuffer.width, framebuffer.height), (32, 8));
+        assert_ne!(framebuffer.handle, handle);
+    }
+}
@@ -0,0 +1,410 @@
+use anyhow::{anyhow, Context, Result};
+use image::{DynamicImage, GrayAlphaImage, GrayImage, ImageBuffer, Luma, LumaA, Rgb, RgbImage, Rgba, RgbaImage};
+use log::info;
+
+use super::material::Material;
+use super::mesh::{Mesh, MeshData, MeshGroup, Vertex};
+use super::obj;
+use super::scene::{NodeId, Scene, Transform};
+use super::texture::{Texture, TextureDescriptor};
+
+/// everything read from a glTF 2.0 / GLB file, kept on the CPU so it can be inspected without a GL context.
+/// `create_meshes` and `create_textures` upload it once a context is current, `instantiate` adds its nodes to a scene.
+#[derive(Debug)]
+pub struct ImportedScene {
+    pub nodes: Vec<ImportedNode>,
+    pub root_nodes: Vec<usize>,
+    pub meshes: Vec<ImportedMesh>,
+    pub materials: Vec<Material>,
+    pub images: Vec<DynamicImage>,
+}
+
+#[derive(Debug)]
+pub struct ImportedNode {
+    pub name: Option<String>,
+    pub translation: glm::Vec3,
+    pub rotation: glm::Quat,
+    pub scale: glm::Vec3,
+    pub mesh: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+#[derive(Debug)]
+pub struct ImportedMesh {
+    pub primitives: Vec<ImportedPrimitive>,
+}
+
+#[derive(Debug)]
+pub struct ImportedPrimitive {
+    pub data: MeshData,
+    pub material: Option<usize>,
+}
+
+impl ImportedScene {
+    pub fn load(source_file: &str) -> Result<Self> {
+        let (document, buffers, images) = gltf::import(source_file).with_context(|| format!("failed to import gltf file {}", source_file))?;
+
+        let scene = ImportedScene::from_document(&document, &buffers, images).with_context(|| format!("failed to read gltf file {}", source_file))?;
+
+        info!(
+            "imported {}: {} nodes, {} meshes, {} materials, {} images",
+            source_file,
+            scene.nodes.len(),
+            scene.meshes.len(),
+            scene.materials.len(),
+            scene.images.len()
+        );
+        return Ok(scene);
+    }
+
+    fn from_document(document: &gltf::Document, buffers: &[gltf::buffer::Data], images: Vec<gltf::image::Data>) -> Result<Self> {
+        let nodes = document.nodes().map(read_node).collect();
+
+        // fall back to every parentless node when the file doesn't name a scene
+        let root_nodes = match document.default_scene().or_else(|| document.scenes().next()) {
+            Some(scene) => scene.nodes().map(|node| node.index()).collect(),
+            None => {
+                let children: Vec<usize> = document.nodes().flat_map(|node| node.children()).map(|child| child.index()).collect();
+                document.nodes().map(|node| node.index()).filter(|index| !children.contains(index)).collect()
+            }
+        };
+
+        let meshes = document.meshes().map(|mesh| read_mesh(&mesh, buffers)).collect::<Result<_>>()?;
+        let materials = document.materials().filter(|material| material.index().is_some()).map(|material| read_material(&material)).collect();
+        let images = images.into_iter().enumerate().map(|(index, image)| read_image(image).with_context(|| format!("image {}", index))).collect::<Result<_>>()?;
+
+        Ok(ImportedScene {
+            nodes,
+            root_nodes,
+            meshes,
+            materials,
+            images,
+        })
+    }
+
+    /// one mesh per primitive, mesh by mesh
+    pub fn create_meshes(&self) -> Result<Vec<Mesh>> {
+        self.meshes.iter().flat_map(|mesh| &mesh.primitives).map(|primitive| Mesh::from_data(&primitive.data)).collect()
+    }
+
+    /// one texture per image
+    pub fn create_textures(&self, descriptor: &TextureDescriptor) -> Result<Vec<Texture>> {
+        self.images.iter().map(|image| Texture::from_image(image, descriptor)).collect()
+    }
+
+    /// the materials with their textures indexed from `first_texture`, where `create_textures` put them
+    pub fn scene_materials(&self, first_texture: usize) -> Vec<Material> {
+        let offset = |texture: Option<usize>| texture.map(|index| first_texture + index);
+        self.materials
+            .iter()
+            .map(|material| Material {
+                base_color_texture: offset(material.base_color_texture),
+                normal_texture: offset(material.normal_texture),
+                metallic_roughness_texture: offset(material.metallic_roughness_texture),
+                ..material.clone()
+            })
+            .collect()
+    }
+
+    /// adds the node hierarchy below `parent`. meshes and materials are indexed from `first_mesh` and `first_material`,
+    /// where `create_meshes` and `scene_materials` put them. a scene node draws one mesh, so every primitive after
+    /// a node's first gets a child of its own
+    pub fn instantiate(&self, scene: &mut Scene, parent: NodeId, first_mesh: usize, first_material: usize) {
+        let first_primitives: Vec<usize> = self
+            .meshes
+            .iter()
+            .scan(first_mesh, |next, mesh| {
+                let first = *next;
+                *next += mesh.primitives.len();
+                Some(first)
+            })
+            .collect();
+
+        let mut stack: Vec<(usize, NodeId)> = self.root_nodes.iter().rev().map(|&root| (root, parent)).collect();
+        while let Some((index, parent)) = stack.pop() {
+            let node = &self.nodes[index];
+            let name = node.name.clone().unwrap_or_else(|| format!("node {}", index));
+            let id = scene.add_node(&name, node.transform(), Some(parent));
+
+            if let Some(mesh) = node.mesh {
+                for (primitive_index, primitive) in self.meshes[mesh].primitives.iter().enumerate() {
+                    let target = match primitive_index {
+                        0 => id,
+                        _ => scene.add_node(&format!("{} primitive {}", name, primitive_index), Transform::default(), Some(id)),
+                    };
+                    scene.set_mesh(target, Some(first_primitives[mesh] + primitive_index));
+                    scene.set_material(target, primitive.material.map(|material| first_material + material));
+                }
+            }
+            stack.extend(node.children.iter().rev().map(|&child| (child, id)));
+        }
+    }
+}
+
+impl ImportedNode {
+    pub fn transform(&self) -> Transform {
+        Transform {
+            translation: self.translation,
+            rotation: self.rotation,
+            scale: self.scale,
+        }
+    }
+}
+
+fn read_node(node: gltf::Node) -> ImportedNode {
+    let (translation, rotation, scale) = node.transform().decomposed();
+
+    ImportedNode {
+        name: node.name().map(str::to_string),
+        translation: translation.into(),
+        rotation: glm::quat(rotation[0], rotation[1], rotation[2], rotation[3]),
+        scale: scale.into(),
+        mesh: node.mesh().map(|mesh| mesh.index()),
+        children: node.children().map(|child| child.index()).collect(),
+    }
+}
+
+fn read_mesh(mesh: &gltf::Mesh, buffers: &[gltf::buffer::Data]) -> Result<ImportedMesh> {
+    let name = mesh.name().map(str::to_string);
+
+    let primitives = mesh
+        .primitives()
+        .map(|primitive| {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                return Err(anyhow!("primitive {} uses {:?}, only triangles are supported", primitive.index(), primitive.mode()));
+            }
+
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+            let positions = reader.read_positions().ok_or_else(|| anyhow!("primitive {} has no positions", primitive.index()))?;
+            let mut vertices: Vec<Vertex> = positions.map(|position| Vertex { position, ..Default::default() }).collect();
+
+            if let Some(uvs) = reader.read_tex_coords(0) {
+                vertices.iter_mut().zip(uvs.into_f32()).for_each(|(vertex, uv)| vertex.uv = uv);
+            }
+
+            let mut indices: Vec<u32> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..vertices.len() as u32).collect(),
+            };
+            check_indices(&indices, vertices.len()).with_context(|| format!("primitive {}", primitive.index()))?;
+
+            match reader.read_normals() {
+                Some(normals) => vertices.iter_mut().zip(normals).for_each(|(vertex, normal)| vertex.normal = normal),
+                None => (vertices, indices) = flat_shaded(&vertices, &indices),
+            }
+
+            let group = MeshGroup {
+                name: name.clone().unwrap_or_else(|| format!("primitive {}", primitive.index())),
+                first_index: 0,
+                index_count: indices.len(),
+            };
+
+            Ok(ImportedPrimitive {
+                data: MeshData {
+                    vertices,
+                    indices,
+                    groups: vec![group],
+                },
+                material: primitive.material().index(),
+            })
+        })
+        .collect::<Result<_>>()
+        .with_context(|| format!("mesh {}", mesh.index()))?;
+
+    Ok(ImportedMesh { primitives })
+}
+
+/// indices must form whole triangles of existing vertices
+fn check_indices(indices: &[u32], vertex_count: usize) -> Result<()> {
+    if !indices.len().is_multiple_of(3) {
+        return Err(anyhow!("{} indices don't form whole triangles", indices.len()));
+    }
+    if let Some(index) = indices.iter().find(|&&index| index as usize >= vertex_count) {
+        return Err(anyhow!("index {} is out of range for {} vertices", index, vertex_count));
+    }
+    Ok(())
+}
+
+/// the spec asks for flat normals when a primitive has none, so every triangle gets its own vertices
+fn flat_shaded(vertices: &[Vertex], indices: &[u32]) -> (Vec<Vertex>, Vec<u32>) {
+    let mut flat = Vec::with_capacity(indices.len());
+    for triangle in indices.chunks_exact(3) {
+        let corners = [vertices[triangle[0] as usize], vertices[triangle[1] as usize], vertices[triangle[2] as usize]];
+        let normal = obj::flat_normal(corners.map(|corner| corner.position));
+        flat.extend(corners.map(|corner| Vertex { normal, ..corner }));
+    }
+    let indices = (0..flat.len() as u32).collect();
+    (flat, indices)
+}
+
+// texture references are resolved straight to the image they sample, samplers are not imported
+fn read_material(material: &gltf::Material) -> Material {
+    let pbr = material.pbr_metallic_roughness();
+
+    Material {
+        name: material.name().map(str::to_string),
+        base_color_factor: pbr.base_color_factor(),
+        base_color_texture: pbr.base_color_texture().map(|info| info.texture().source().index()),
+        normal_texture: material.normal_texture().map(|normal| normal.texture().source().index()),
+        metallic_factor: pbr.metallic_factor(),
+        roughness_factor: pbr.roughness_factor(),
+        metallic_roughness_texture: pbr.metallic_roughness_texture().map(|info| info.texture().source().index()),
+    }
+}
+
+fn read_image(image: gltf::image::Data) -> Result<DynamicImage> {
+    use gltf::image::Format;
+
+    let (width, height) = (image.width, image.height);
+    let size_error = || anyhow!("pixel data does not match {}x{} {:?}", width, height, image.format);
+
+    let image = match image.format {
+        Format::R8 => DynamicImage::ImageLuma8(GrayImage::from_raw(width, height, image.pixels).ok_or_else(size_error)?),
+        Format::R8G8 => DynamicImage::ImageLumaA8(GrayAlphaImage::from_raw(width, height, image.pixels).ok_or_else(size_error)?),
+        Format::R8G8B8 => DynamicImage::ImageRgb8(RgbImage::from_raw(width, height, image.pixels).ok_or_else(size_error)?),
+        Format::R8G8B8A8 => DynamicImage::ImageRgba8(RgbaImage::from_raw(width, height, image.pixels).ok_or_else(size_error)?),
+        // textures are uploaded as RGBA8 anyway, so higher precision formats are reduced here
+        Format::R16 => DynamicImage::ImageLuma16(ImageBuffer::<Luma<u16>, _>::from_raw(width, height, u16_pixels(&image.pixels)).ok_or_else(size_error)?),
+        Format::R16G16 => DynamicImage::ImageLumaA16(ImageBuffer::<LumaA<u16>, _>::from_raw(width, height, u16_pixels(&image.pixels)).ok_or_else(size_error)?),
+        Format::R16G16B16 => DynamicImage::ImageRgb16(ImageBuffer::<Rgb<u16>, _>::from_raw(width, height, u16_pixels(&image.pixels)).ok_or_else(size_error)?),
+        Format::R16G16B16A16 => DynamicImage::ImageRgba16(ImageBuffer::<Rgba<u16>, _>::from_raw(width, height, u16_pixels(&image.pixels)).ok_or_else(size_error)?),
+        Format::R32G32B32FLOAT => DynamicImage::ImageRgb32F(ImageBuffer::<Rgb<f32>, _>::from_raw(width, height, f32_pixels(&image.pixels)).ok_or_else(size_error)?),
+        Format::R32G32B32A32FLOAT => DynamicImage::ImageRgba32F(ImageBuffer::<Rgba<f32>, _>::from_raw(width, height, f32_pixels(&image.pixels)).ok_or_else(size_error)?),
+    };
+
+    Ok(match image {
+        DynamicImage::ImageLuma8(_) | DynamicImage::ImageLumaA8(_) | DynamicImage::ImageRgb8(_) | DynamicImage::ImageRgba8(_) => image,
+        _ => DynamicImage::ImageRgba8(image.to_rgba8()),
+    })
+}
+
+// gltf decodes into native endian channels
+fn u16_pixels(bytes: &[u8]) -> Vec<u16> {
+    bytes.chunks_exact(2).map(|channel| u16::from_ne_bytes([channel[0], channel[1]])).collect()
+}
+
+fn f32_pixels(bytes: &[u8]) -> Vec<f32> {
+    bytes.chunks_exact(4).map(|channel| f32::from_ne_bytes([channel[0], channel[1], channel[2], channel[3]])).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/fixtures/gltf/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    fn assert_fixture_scene(scene: &ImportedScene) {
+        let names: Vec<_> = scene.nodes.iter().map(|node| node.name.as_deref().unwrap()).collect();
+        assert_eq!(names, vec!["root", "child", "rotated"]);
+        assert_eq!(scene.root_nodes, vec![0, 2]);
+        assert_eq!(scene.nodes[0].children, vec![1]);
+        assert_eq!(scene.nodes[1].mesh, Some(0));
+
+        let primitive = &scene.meshes[0].primitives[0];
+        assert_eq!(primitive.data.indices, vec![0, 1, 2]);
+        assert_eq!(primitive.data.vertices[1].position, [1.0, 0.0, 0.0]);
+        assert_eq!(primitive.data.vertices[2].uv, [0.0, 1.0]);
+        assert_eq!(primitive.material, Some(0));
+
+        let material = &scene.materials[0];
+        assert_eq!(material.name.as_deref(), Some("stone"));
+        assert_eq!(material.base_color_factor, [1.0, 0.5, 0.25, 1.0]);
+        assert_eq!(material.base_color_texture, Some(0));
+        assert_eq!(material.normal_texture, Some(0));
+        assert_eq!(material.metallic_roughness_texture, Some(0));
+        assert_eq!(material.metallic_factor, 0.25);
+        assert_eq!(material.roughness_factor, 0.75);
+
+        assert_eq!(scene.images.len(), 1);
+        assert_eq!((scene.images[0].width(), scene.images[0].height()), (2, 2));
+        assert_eq!(scene.images[0].to_rgba8().get_pixel(1, 1).0, [255, 255, 255, 128]);
+    }
+
+    #[test]
+    fn imports_embedded_gltf() {
+        assert_fixture_scene(&ImportedScene::load(&fixture("triangle.gltf")).unwrap());
+    }
+
+    #[test]
+    fn imports_glb() {
+        assert_fixture_scene(&ImportedScene::load(&fixture("triangle.glb")).unwrap());
+    }
+
+    #[test]
+    fn instantiates_the_hierarchy_below_a_scene_node() {
+        let imported = ImportedScene::load(&fixture("triangle.gltf")).unwrap();
+        let mut scene = Scene::new();
+        let parent = scene.add_node("model", Transform::from_translation(glm::vec3(0.0, 0.0, -1.0)), None);
+        imported.instantiate(&mut scene, parent, 3, 5);
+        scene.update_world_matrices();
+
+        let nodes: Vec<_> = scene.iter().map(|(_, node)| (node.name.as_str(), node.mesh, node.material)).collect();
+        assert_eq!(nodes, [("model", None, None), ("root", None, None), ("child", Some(3), Some(5)), ("rotated", None, None)]);
+
+        let world = |name: &str| *scene.iter().find(|(_, node)| node.name == name).unwrap().1.world_matrix();
+        // child: translated by (0, 2, 0) and scaled by 2 under a parent translated by (1, 0, 0), all under the model node
+        let child_origin = world("child") * glm::vec4(0.0, 0.0, 0.0, 1.0);
+        let child_x = world("child") * glm::vec4(1.0, 0.0, 0.0, 1.0);
+        assert!(glm::distance(&child_origin.xyz(), &glm::vec3(1.0, 2.0, -1.0)) < 1e-5);
+        assert!(glm::distance(&child_x.xyz(), &glm::vec3(3.0, 2.0, -1.0)) < 1e-5);
+
+        // rotated: 90 degrees about y sends +x to -z
+        let rotated_x = world("rotated") * glm::vec4(1.0, 0.0, 0.0, 0.0);
+        assert!(glm::distance(&rotated_x.xyz(), &glm::vec3(0.0, 0.0, -1.0)) < 1e-5);
+    }
+
+    #[test]
+    fn offsets_material_textures() {
+        let imported = ImportedScene::load(&fixture("triangle.gltf")).unwrap();
+        let material = &imported.scene_materials(4)[0];
+
+        assert_eq!(material.base_color_texture, Some(4));
+        assert_eq!(material.normal_texture, Some(4));
+        assert_eq!(material.metallic_roughness_texture, Some(4));
+        assert_eq!(material.base_color_factor, [1.0, 0.5, 0.25, 1.0]);
+    }
+
+    #[test]
+    fn primitives_without_normals_are_flat_shaded() {
+        let vertex = |position| Vertex { position, ..Default::default() };
+        // a quad folded along its diagonal, sharing two vertices between the halves
+        let vertices = [vertex([0.0, 0.0, 0.0]), vertex([1.0, 0.0, 0.0]), vertex([0.0, 1.0, 0.0]), vertex([1.0, 1.0, -1.0])];
+
+        let (flat, indices) = flat_shaded(&vertices, &[0, 1, 2, 2, 1, 3]);
+        assert_eq!(indices, (0..6).collect::<Vec<u32>>());
+        assert_eq!(flat[4].position, [1.0, 0.0, 0.0]);
+        assert!(flat[..3].iter().all(|v| v.normal == [0.0, 0.0, 1.0]));
+        let folded = glm::vec3(1.0, 1.0, 1.0).normalize();
+        assert!(flat[3..].iter().all(|v| glm::distance(&glm::Vec3::from(v.normal), &folded) < 1e-5));
+    }
+
+    #[test]
+    fn rejects_partial_triangles_and_out_of_range_indices() {
+        assert!(check_indices(&[0, 1, 2, 2, 1, 3], 4).is_ok());
+        assert_eq!(check_indices(&[0, 1, 2, 2], 4).unwrap_err().to_string(), "4 indices don't form whole triangles");
+        assert_eq!(check_indices(&[0, 1, 4], 4).unwrap_err().to_string(), "index 4 is out of range for 4 vertices");
+    }
+
+    #[test]
+    fn converts_high_precision_images() {
+        let pixels: Vec<u8> = [0u16, 0x8080, 0xffff, 0xffff].iter().flat_map(|channel| channel.to_ne_bytes()).collect();
+        let image = gltf::image::Data {
+            pixels,
+            format: gltf::image::Format::R16G16B16A16,
+            width: 1,
+            height: 1,
+        };
+
+        let image = read_image(image).unwrap();
+        assert_eq!(image.as_rgba8().unwrap().get_pixel(0, 0).0, [0, 128, 255, 255]);
+    }
+
+    #[test]
+    fn reports_missing_file() {
+        let error = ImportedScene::load(&fixture("missing.gltf")).unwrap_err();
+        assert!(format!("{:#}", error).contains("missing.gltf"));
+    }
+}
@@ -0,0 +1,86 @@
+use anyhow::Result;
+use gl::types::*;
+use std::ffi::c_void;
+use std::fmt;
+
+use super::buffer::{Buffer, BufferStorage, BufferTarget};
+use super::gl;
+
+/// integer types GL accepts as element indices
+pub trait Index: Copy {
+    const GL_TYPE: GLenum;
+
+    fn into_storage(buffer: Buffer<Self>) -> IndexStorage;
+}
+
+impl Index for u16 {
+    const GL_TYPE: GLenum = gl::UNSIGNED_SHORT;
+
+    fn into_storage(buffer: Buffer<Self>) -> IndexStorage {
+        IndexStorage::U16(buffer)
+    }
+}
+
+impl Index for u32 {
+    const GL_TYPE: GLenum = gl::UNSIGNED_INT;
+
+    fn into_storage(buffer: Buffer<Self>) -> IndexStorage {
+        IndexStorage::U32(buffer)
+    }
+}
+
+pub enum IndexStorage {
+    U16(Buffer<u16>),
+    U32(Buffer<u32>),
+}
+
+pub struct IndexBuffer {
+    buffer: IndexStorage,
+    pub count: usize,
+    pub index_type: GLenum,
+    index_size: usize,
+}
+
+impl IndexBuffer {
+    pub fn new<I: Index>(indices: &[I]) -> Result<Self> {
+        let buffer = Buffer::new(BufferTarget::INDEX, BufferStorage::IMMUTABLE, indices)?;
+
+        return Ok(IndexBuffer {
+            buffer: I::into_storage(buffer),
+            count: indices.len(),
+            index_type: I::GL_TYPE,
+            index_size: size_of::<I>(),
+        });
+    }
+
+    /// uses 16 bit indices when every index fits, halving the buffer size for small meshes
+    pub fn compact(indices: &[u32]) -> Result<Self> {
+        if indices.iter().all(|&index| index <= u16::MAX as u32) {
+            let indices: Vec<u16> = indices.iter().map(|&index| index as u16).collect();
+            IndexBuffer::new(&indices)
+        } else {
+            IndexBuffer::new(indices)
+        }
+    }
+
+    pub fn handle(&self) -> u32 {
+        match &self.buffer {
+            IndexStorage::U16(buffer) => buffer.handle,
+            IndexStorage::U32(buffer) => buffer.handle,
+        }
+    }
+
+    /// byte offset of `first_index`, as expected by the `indices` argument of glDrawElements
+    pub fn offset(&self, first_index: usize) -> *const c_void {
+        (first_index * self.index_size) as *const c_void
+    }
+}
+
+impl fmt::Display for IndexBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.buffer {
+            IndexStorage::U16(buffer) => write!(f, "{} (u16)", buffer),
+            IndexStorage::U32(buffer) => write!(f, "{} (u32)", buffer),
+        }
+    }
+}
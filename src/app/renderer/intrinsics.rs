@@ -0,0 +1,112 @@
+//! camera intrinsics: how wide the camera sees, independent of where it is.
+//! angles are wrapped in `Degrees`/`Radians` so a value can't be passed in the wrong unit.
+
+/// height of a 35mm full frame sensor, the usual reference when quoting focal lengths
+pub const FULL_FRAME_SENSOR_HEIGHT: f32 = 24.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct Degrees(pub f32);
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct Radians(pub f32);
+
+impl From<Degrees> for Radians {
+    fn from(degrees: Degrees) -> Self {
+        Radians(degrees.0.to_radians())
+    }
+}
+
+impl From<Radians> for Degrees {
+    fn from(radians: Radians) -> Self {
+        Degrees(radians.0.to_degrees())
+    }
+}
+
+/// field of view as it was specified, converted to whatever the projection needs once the aspect is known
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FieldOfView {
+    /// angle between the top and bottom edges of the view
+    Vertical(Radians),
+    /// angle between the left and right edges, the vertical angle then shrinks as the display gets wider
+    Horizontal(Radians),
+    /// lens focal length and sensor height in the same units, usually millimetres
+    FocalLength { focal_length: f32, sensor_height: f32 },
+}
+
+impl FieldOfView {
+    #[allow(unused)]
+    pub fn vertical_degrees(degrees: f32) -> Self {
+        FieldOfView::Vertical(Degrees(degrees).into())
+    }
+
+    /// a lens on a 35mm full frame camera, e.g. 50 for a standard lens
+    #[allow(unused)]
+    pub fn full_frame_lens(focal_length: f32) -> Self {
+        FieldOfView::FocalLength {
+            focal_length,
+            sensor_height: FULL_FRAME_SENSOR_HEIGHT,
+        }
+    }
+
+    /// `display_aspect` is width / height
+    pub fn vertical(&self, display_aspect: f32) -> Radians {
+        match *self {
+            FieldOfView::Vertical(angle) => angle,
+            FieldOfView::Horizontal(angle) => Radians(2.0 * ((angle.0 / 2.0).tan() / display_aspect).atan()),
+            FieldOfView::FocalLength { focal_length, sensor_height } => Radians(2.0 * (sensor_height / (2.0 * focal_length)).atan()),
+        }
+    }
+
+    #[allow(unused)]
+    pub fn horizontal(&self, display_aspect: f32) -> Radians {
+        match *self {
+            FieldOfView::Horizontal(angle) => angle,
+            _ => Radians(2.0 * ((self.vertical(display_aspect).0 / 2.0).tan() * display_aspect).atan()),
+        }
+    }
+
+    /// focal length in pixels for a display `display_height` pixels tall, the `fy` of a pinhole camera matrix
+    #[allow(unused)]
+    pub fn focal_length_pixels(&self, display_aspect: f32, display_height: f32) -> f32 {
+        display_height / (2.0 * (self.vertical(display_aspect).0 / 2.0).tan())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn converts_between_degrees_and_radians() {
+        assert_close(Radians::from(Degrees(180.0)).0, std::f32::consts::PI);
+        assert_close(Degrees::from(Radians(FRAC_PI_2)).0, 90.0);
+    }
+
+    #[test]
+    fn horizontal_and_vertical_agree_through_aspect() {
+        // 90 degrees across a 2:1 display sees tan(45) = 1 to each side, so half that vertically
+        let horizontal = FieldOfView::Horizontal(Radians(FRAC_PI_2));
+        assert_close(horizontal.vertical(2.0).0, 2.0 * 0.5_f32.atan());
+        assert_close(horizontal.horizontal(2.0).0, FRAC_PI_2);
+
+        let vertical = FieldOfView::Vertical(horizontal.vertical(2.0));
+        assert_close(vertical.horizontal(2.0).0, FRAC_PI_2);
+    }
+
+    #[test]
+    fn focal_length_matches_lens_tables() {
+        // a 50mm lens on full frame is quoted as 27.0 degrees vertically, 39.6 horizontally
+        let lens = FieldOfView::full_frame_lens(50.0);
+        assert!((Degrees::from(lens.vertical(1.5)).0 - 27.0).abs() < 0.05);
+        assert!((Degrees::from(lens.horizontal(1.5)).0 - 39.6).abs() < 0.05);
+
+        // a 90 degree view is as far from the image plane as half its height
+        assert_close(FieldOfView::vertical_degrees(90.0).focal_length_pixels(1.0, 600.0), 300.0);
+    }
+}
@@ -0,0 +1,101 @@
+pub use threed_derive::Std140;
+
+/// a type that can be a member of a std140/std430 block. Only the types whose layout is the same under
+/// both rules are implemented, arrays (where the rules differ) have to be split into separate fields.
+pub trait GlslField {
+    const GLSL_TYPE: &'static str;
+    const ALIGN: usize;
+    const SIZE: usize;
+}
+
+/// implemented by `#[derive(Std140)]` / `#[derive(Std430)]`, which also checks the Rust layout at compile time
+#[allow(unused)]
+pub trait GlslBlock: Copy {
+    const LAYOUT: &'static str;
+    /// (GLSL type, name) of every non padding field in declaration order
+    const MEMBERS: &'static [(&'static str, &'static str)];
+
+    /// GLSL declaration of the block, `storage` is `uniform` or `buffer`
+    fn glsl_declaration(storage: &str, block_name: &str, binding: u32) -> String {
+        let mut declaration = format!("layout ({}, binding = {}) {} {} {{\n", Self::LAYOUT, binding, storage, block_name);
+        for (glsl_type, name) in Self::MEMBERS {
+            declaration.push_str(&format!("  {} {};\n", glsl_type, name));
+        }
+        declaration.push_str("};\n");
+        declaration
+    }
+}
+
+pub const fn align_up(offset: usize, align: usize) -> usize {
+    offset.div_ceil(align) * align
+}
+
+macro_rules! glsl_field {
+    ($rust_type:ty, $glsl_type:literal, $align:literal, $size:literal) => {
+        impl GlslField for $rust_type {
+            const GLSL_TYPE: &'static str = $glsl_type;
+            const ALIGN: usize = $align;
+            const SIZE: usize = $size;
+        }
+    };
+}
+
+glsl_field!(f32, "float", 4, 4);
+glsl_field!(i32, "int", 4, 4);
+glsl_field!(u32, "uint", 4, 4);
+glsl_field!([f32; 2], "vec2", 8, 8);
+glsl_field!([f32; 3], "vec3", 16, 12);
+glsl_field!([f32; 4], "vec4", 16, 16);
+glsl_field!([f32; 16], "mat4", 16, 64);
+glsl_field!(glm::Vec2, "vec2", 8, 8);
+glsl_field!(glm::Vec3, "vec3", 16, 12);
+glsl_field!(glm::Vec4, "vec4", 16, 16);
+glsl_field!(glm::Mat4, "mat4", 16, 64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use threed_derive::Std430;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Std140)]
+    struct Light {
+        position: [f32; 3],
+        intensity: f32,
+        colour: glm::Vec3,
+        _padding: [u32; 1],
+        view: glm::Mat4,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Std430)]
+    struct Particle {
+        age: f32,
+        mass: f32,
+        charge: f32,
+    }
+
+    #[test]
+    fn generates_std140_declaration() {
+        assert_eq!(
+            Light::glsl_declaration("uniform", "lightData", 2),
+            "layout (std140, binding = 2) uniform lightData {\n  vec3 position;\n  float intensity;\n  vec3 colour;\n  mat4 view;\n};\n"
+        );
+    }
+
+    #[test]
+    fn std430_does_not_round_struct_alignment() {
+        // 12 bytes would be padded to 16 under std140
+        assert_eq!(size_of::<Particle>(), 12);
+        assert_eq!(Particle::LAYOUT, "std430");
+        assert_eq!(Particle::MEMBERS, &[("float", "age"), ("float", "mass"), ("float", "charge")]);
+    }
+
+    #[test]
+    fn aligns_offsets() {
+        assert_eq!(align_up(0, 16), 0);
+        assert_eq!(align_up(12, 16), 16);
+        assert_eq!(align_up(68, 16), 80);
+        assert_eq!(align_up(4, 8), 8);
+    }
+}
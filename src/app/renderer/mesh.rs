@@ -0,0 +1,162 @@
+use anyhow::Result;
+use log::info;
+use std::fmt;
+use std::mem::offset_of;
+
+use super::buffer::{Buffer, BufferStorage, BufferTarget};
+use super::index_buffer::IndexBuffer;
+use super::obj;
+use super::vertex_array_objects::VertexArrayObjects;
+
+/// vertex layout shared by every mesh, matches the `in` attributes of the vertex shaders
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+}
+
+/// a named range of the index buffer, e.g. an OBJ `g`/`o` group
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeshGroup {
+    pub name: String,
+    pub first_index: usize,
+    pub index_count: usize,
+}
+
+/// axis aligned bounding box
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub min: glm::Vec3,
+    pub max: glm::Vec3,
+}
+
+impl Bounds {
+    pub fn centre(&self) -> glm::Vec3 {
+        (self.min + self.max) / 2.0
+    }
+
+    /// radius of the bounding sphere around `centre`
+    pub fn radius(&self) -> f32 {
+        glm::distance(&self.min, &self.max) / 2.0
+    }
+
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        Bounds {
+            min: glm::min2(&self.min, &other.min),
+            max: glm::max2(&self.max, &other.max),
+        }
+    }
+
+    /// axis aligned bounds around all 8 corners after `matrix`, so they grow when rotated
+    pub fn transformed(&self, matrix: &glm::Mat4) -> Bounds {
+        let corner = |i: usize| {
+            let corner = glm::vec4(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+                1.0,
+            );
+            (matrix * corner).xyz()
+        };
+        (1..8).map(corner).fold(Bounds { min: corner(0), max: corner(0) }, |bounds, point| bounds.union(&Bounds { min: point, max: point }))
+    }
+}
+
+/// CPU side geometry, produced by the loaders and uploaded by `Mesh`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub groups: Vec<MeshGroup>,
+}
+
+impl MeshData {
+    /// bounds of the vertices, `None` for an empty mesh
+    pub fn bounds(&self) -> Option<Bounds> {
+        let first = glm::Vec3::from(self.vertices.first()?.position);
+        let bounds = self.vertices.iter().fold(Bounds { min: first, max: first }, |bounds, vertex| {
+            let position = glm::Vec3::from(vertex.position);
+            Bounds {
+                min: glm::min2(&bounds.min, &position),
+                max: glm::max2(&bounds.max, &position),
+            }
+        });
+        Some(bounds)
+    }
+}
+
+#[allow(unused)]
+pub struct Mesh {
+    pub vertex_array_object: VertexArrayObjects,
+    vertex_buffer: Buffer<Vertex>,
+    pub index_buffer: IndexBuffer,
+    pub groups: Vec<MeshGroup>,
+    /// `None` for a mesh with no vertices
+    pub bounds: Option<Bounds>,
+}
+
+const POSITION_LOCATION: u32 = 0;
+const NORMAL_LOCATION: u32 = 1;
+const UV_LOCATION: u32 = 2;
+const VERTEX_BINDING: u32 = 0;
+
+impl Mesh {
+    pub fn new(source_file: &str) -> Result<Self> {
+        let data = obj::load(source_file)?;
+        let mesh = Mesh::from_data(&data)?;
+        info!("created {} from {} ({} vertices, {} groups)", mesh, source_file, data.vertices.len(), data.groups.len());
+        return Ok(mesh);
+    }
+
+    pub fn from_data(data: &MeshData) -> Result<Self> {
+        let bounds = data.bounds();
+        let vertex_array_object = VertexArrayObjects::new()?;
+        let vertex_buffer = Buffer::new(BufferTarget::VERTEX, BufferStorage::IMMUTABLE, &data.vertices)?;
+        let index_buffer = IndexBuffer::compact(&data.indices)?;
+
+        unsafe {
+            vertex_array_object.vertex_buffer(VERTEX_BINDING, &vertex_buffer);
+            vertex_array_object.attribute(POSITION_LOCATION, VERTEX_BINDING, 3, offset_of!(Vertex, position) as u32);
+            vertex_array_object.attribute(NORMAL_LOCATION, VERTEX_BINDING, 3, offset_of!(Vertex, normal) as u32);
+            vertex_array_object.attribute(UV_LOCATION, VERTEX_BINDING, 2, offset_of!(Vertex, uv) as u32);
+            vertex_array_object.element_buffer(&index_buffer);
+        }
+
+        return Ok(Mesh {
+            vertex_array_object,
+            vertex_buffer,
+            index_buffer,
+            groups: data.groups.clone(),
+            bounds,
+        });
+    }
+
+}
+
+impl fmt::Display for Mesh {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "mesh ({})", self.vertex_array_object)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::headless::gl_test_context;
+    use crate::app::renderer::gl;
+
+    #[test]
+    fn empty_meshes_can_be_created_and_drawn() {
+        let _context = gl_test_context();
+
+        let mesh = Mesh::from_data(&MeshData::default()).unwrap();
+        assert_eq!(mesh.bounds, None);
+        assert_eq!(mesh.index_buffer.count, 0);
+        unsafe {
+            mesh.vertex_array_object.bind();
+            gl::DrawElements(gl::TRIANGLES, 0, mesh.index_buffer.index_type, mesh.index_buffer.offset(0));
+        }
+    }
+}
@@ -1,37 +1,96 @@
 use gl::types::*;
-use anyhow::Result;
+use anyhow::{Context, Result};
+use image::{imageops, RgbaImage};
 use glutin::display::GlDisplay;
 use log::{error, info};
 use std::os::raw;
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
 use std::ffi::{c_void, CStr, CString};
+use std::fs;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 mod shader;
-use shader::{Shader, ShaderType};
+mod preprocessor;
+mod shader_error;
 mod program;
-use program::Program;
+mod reflection;
+mod shader_watcher;
+mod program_cache;
+pub use program_cache::{ProgramCache, ProgramKey};
+mod program_binary_cache;
+pub use program_binary_cache::ProgramBinaryCache;
+mod compute_program;
 mod vertex_array_objects;
 use vertex_array_objects::VertexArrayObjects;
+mod index_buffer;
+use index_buffer::IndexBuffer;
 mod texture;
-use texture::Texture;
-mod vertex_buffer_objects;
-use vertex_buffer_objects::VertexBufferObjects;
+pub use texture::Texture;
+mod sampler;
+mod buffer;
+pub use buffer::{Buffer, BufferStorage, BufferTarget};
+mod layout;
+use layout::Std140;
+mod framebuffer;
+pub use framebuffer::{ColorFormat, DepthAttachment, DepthFormat, Framebuffer, FramebufferDescriptor};
+mod camera;
+pub use camera::{Camera, OrbitCamera};
+mod intrinsics;
+pub use intrinsics::{FieldOfView, Radians};
+mod projection;
+pub use projection::Projection;
+mod scene;
+pub use scene::Scene;
+#[cfg(test)]
+pub use scene::Transform;
+mod mesh;
+use mesh::Bounds;
+pub use mesh::Mesh;
+mod obj;
+mod material;
+use material::Material;
+mod scene_file;
+pub use scene_file::SceneDescription;
+use scene_file::Light;
+mod gltf_import;
 
 pub mod gl;
 
+// 45 degrees vertically
+const DEFAULT_FIELD_OF_VIEW: FieldOfView = FieldOfView::Vertical(Radians(FRAC_PI_4));
+const DEFAULT_FAR_CLIPPING_PLANE: f32 = 1000.0;
+// distance from the camera to what it looks at, when the camera itself doesn't say
+const DEFAULT_FOCUS_DISTANCE: f32 = 3.5;
+const SHADER_POLL_INTERVAL: Duration = Duration::from_millis(250);
+const VERTEX_SHADER: &str = "shaders/vertex.glsl";
+const FRAGMENT_SHADER: &str = "shaders/fragment.glsl";
+// linked programs from previous launches, see `ProgramBinaryCache`
+const PROGRAM_BINARY_DIRECTORY: &str = "program_cache";
+
 pub struct DrawConfig {
-    field_of_view: f32,
-    near_clipping_plane: f32,
-    far_clipping_plane: f32,
+    projection: Projection,
     display_dimensions: (i32, i32),
     display_aspect: f32,
 }
 
+fn default_projection() -> Projection {
+    Projection::Perspective {
+        field_of_view: DEFAULT_FIELD_OF_VIEW,
+        near_clipping_plane: 0.1,
+        far_clipping_plane: DEFAULT_FAR_CLIPPING_PLANE,
+    }
+}
+
+fn default_camera() -> Camera {
+    Camera::Orbit(OrbitCamera::new(glm::Vec3::zeros(), DEFAULT_FOCUS_DISTANCE))
+}
+
 impl DrawConfig {
     fn new((width, height): (i32, i32)) -> Self {
         return DrawConfig {
-            field_of_view: 45.0,
-            near_clipping_plane: 0.1,
-            far_clipping_plane: 1000.0,
+            projection: default_projection(),
             display_dimensions: (width, height),
             display_aspect: width as f32 / height as f32,
         };
@@ -41,19 +100,27 @@ impl DrawConfig {
 
 #[allow(unused)]
 pub struct Renderer {
-    vertex_shader: Shader,
-    fragment_shader: Shader,
-    program: Program,
-    vertex_array_object: VertexArrayObjects,
-    per_frame_buffer_object: VertexBufferObjects<PerFrameData>,
-    texture: Texture,
+    programs: ProgramCache,
+    meshes: Vec<Mesh>,
+    textures: Vec<Texture>,
+    materials: Vec<Material>,
+    lights: Vec<Light>,
+    scene: Scene,
+    // what was loaded, saving writes it back with the live scene, camera and projection
+    scene_description: SceneDescription,
+    per_frame_buffer_object: Buffer<PerFrameData>,
     draw_config: DrawConfig,
+    render_target: Option<Framebuffer>,
+    camera: Camera,
 }
 
 #[repr(C)]
+#[derive(Clone, Copy, Std140)]
 struct PerFrameData {
     perspective_transform: [f32; 16],
     wire_frame_enabled: u32,
+    _padding: [u32; 3],
+    base_color_factor: [f32; 4],
 }
 
 impl Renderer {
@@ -80,27 +147,20 @@ impl Renderer {
             gl::Enable(gl::DEBUG_OUTPUT);
         }
 
-        let vertex_shader = Shader::new(ShaderType::VERTEX, "shaders/vertex_tex.glsl").unwrap();
-        let fragment_shader = Shader::new(ShaderType::FRAGMENT, "shaders/fragment_tex.glsl").unwrap();
-        let program = Program::new(&vertex_shader, &fragment_shader)?;
-
-        let texture = Texture::new("textures/stone.png").unwrap();
+        // compile the common variants up front so broken shaders fail at startup rather than on first draw
+        let mut programs = ProgramCache::new(SHADER_POLL_INTERVAL, ProgramBinaryCache::new(PROGRAM_BINARY_DIRECTORY));
+        for textured in [true, false] {
+            let program = programs.get(&material_program_key(textured))?;
+            program.reflection.check_uniform_block_size("perFrameData", size_of::<PerFrameData>())?;
+        }
 
-        let vertex_array_object = VertexArrayObjects::new().unwrap();
-        let per_frame_buffer_object = VertexBufferObjects::new().unwrap();
+        let per_frame_buffer_object = Buffer::with_len(BufferTarget::UNIFORM, BufferStorage::DYNAMIC, 1)?;
 
         unsafe {
-            program.use_program();
-
-            vertex_array_object.bind();
-    
-            texture.bind();
-
-            per_frame_buffer_object.bind();
+            per_frame_buffer_object.bind(0);
 
             gl::ClearColor(1.0, 1.0, 1.0, 1.0);
             gl::Enable(gl::DEPTH_TEST);
-            gl::DepthFunc(gl::LESS);
             gl::Enable(gl::POLYGON_OFFSET_LINE);
             gl::PolygonOffset(-1.0, -1.0);
         }
@@ -108,55 +168,204 @@ impl Renderer {
         let draw_config = DrawConfig::new((300, 300));
 
         Ok(Self {
-            texture,
-            vertex_shader,
-            fragment_shader,
-            program,
-            vertex_array_object,
+            programs,
+            meshes: Vec::new(),
+            textures: Vec::new(),
+            materials: Vec::new(),
+            lights: Vec::new(),
+            scene: Scene::new(),
+            scene_description: SceneDescription::default(),
             per_frame_buffer_object,
             draw_config,
+            render_target: None,
+            camera: default_camera(),
         })
     }
 
-    pub fn draw(&mut self, delta: f32, _frame_delta: f32) {
+    pub fn draw(&mut self) {
         unsafe {
-            let identity_matrix = glm::Mat4::identity();
-            let translation_vector = glm::vec3(0.0, 0.0, -3.5);
-            let translation_matrix = glm::translate(&identity_matrix, &translation_vector);
-            let rotation_vec = glm::vec3(1.0, 1.0, 1.0);
-
-            let translation_matrix = glm::rotate(&translation_matrix, delta, &rotation_vec);
-
-            let perspective_matrix = glm::perspective(
-                self.draw_config.display_aspect,
-                self.draw_config.field_of_view,
-                self.draw_config.near_clipping_plane,
-                self.draw_config.far_clipping_plane,
-            );
+            match &self.render_target {
+                Some(framebuffer) => framebuffer.bind(),
+                None => gl::BindFramebuffer(gl::FRAMEBUFFER, 0),
+            }
 
-            let translation_matrix = perspective_matrix * translation_matrix;
-            let translation_matrix_slice = translation_matrix.as_slice();
+            self.scene.update_world_matrices();
 
-            let per_frame_date = PerFrameData {
-                perspective_transform: translation_matrix_slice.try_into().expect("slice is incorrect length"),
-                wire_frame_enabled: 0,
-            };
+            let perspective_matrix = self.draw_config.projection.matrix(self.draw_config.display_aspect);
+            let view_projection_matrix = perspective_matrix * self.camera.view_matrix();
 
+            self.draw_config.projection.apply_depth_state();
             gl::ClearColor(0.1, 0.1, 0.1, 0.9);
             gl::Clear(gl::COLOR_BUFFER_BIT);
             gl::Clear(gl::DEPTH_BUFFER_BIT);
 
-            self.per_frame_buffer_object.sub_buffer(per_frame_date);
-
             gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
-            gl::DrawArrays(gl::TRIANGLES, 0, 36);
 
-            //gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
-            //gl::DrawArrays(gl::TRIANGLES, 0, 36);
+            for (_, node) in self.scene.iter() {
+                let Some(mesh) = node.mesh.map(|index| &self.meshes[index]) else {
+                    continue;
+                };
+
+                let material = node.material.map(|index| &self.materials[index]);
+                let texture = material.and_then(|material| material.base_color_texture).map(|index| &self.textures[index]);
+                // a variant that failed to compile has been logged already, leave its nodes out
+                let Ok(program) = self.programs.get(&material_program_key(texture.is_some())) else {
+                    continue;
+                };
+                program.use_program();
+                if let Some(texture) = texture {
+                    texture.bind(0);
+                }
+
+                let translation_matrix = view_projection_matrix * node.world_matrix();
+                let translation_matrix_slice = translation_matrix.as_slice();
+
+                let per_frame_date = PerFrameData {
+                    perspective_transform: translation_matrix_slice.try_into().expect("slice is incorrect length"),
+                    wire_frame_enabled: 0,
+                    _padding: [0; 3],
+                    base_color_factor: material.map_or([1.0; 4], |material| material.base_color_factor),
+                };
+                self.per_frame_buffer_object.sub_data(0, &[per_frame_date]).unwrap();
+
+                self.draw_indexed(&mesh.vertex_array_object, &mesh.index_buffer, 0..mesh.index_buffer.count);
+
+                //gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
+                //self.draw_indexed(&mesh.vertex_array_object, &mesh.index_buffer, 0..mesh.index_buffer.count);
+            }
+        }
+    }
+
+    /// recompiles the program variants whose shader sources have been saved.
+    /// if anything fails the compiler log is printed and the old programs stay in use
+    pub fn reload_changed_shaders(&mut self) {
+        self.programs.reload_changed();
+    }
+
+    /// draws `indices` of `index_buffer` as triangles, the element buffer must already be attached to `vertex_array_object`
+    pub unsafe fn draw_indexed(&self, vertex_array_object: &VertexArrayObjects, index_buffer: &IndexBuffer, indices: Range<usize>) {
+        vertex_array_object.bind();
+        gl::DrawElements(gl::TRIANGLES, indices.len() as GLsizei, index_buffer.index_type, index_buffer.offset(indices.start));
+    }
+
+    /// reads the bound framebuffer back at the current display size, flipped so the top row comes first
+    pub fn read_pixels(&self) -> RgbaImage {
+        let (width, height) = self.draw_config.display_dimensions;
+        let mut pixels: Vec<u8> = vec![0; (width * height * 4) as usize];
+
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(0, 0, width, height, gl::RGBA, gl::UNSIGNED_BYTE, pixels.as_mut_ptr() as *mut c_void);
+        }
+
+        let image = RgbaImage::from_raw(width as u32, height as u32, pixels).expect("pixel buffer is incorrect length");
+        imageops::flip_vertical(&image)
+    }
+
+    /// saves the default framebuffer to `<directory>/screenshot-<unix time ms>.png`.
+    /// call after drawing and before swapping buffers, the back buffer is undefined after a swap
+    pub fn save_screenshot(&self, directory: &str) -> Result<PathBuf> {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+        let image = self.read_pixels();
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+        fs::create_dir_all(directory).with_context(|| format!("failed to create screenshot directory {}", directory))?;
+        let path = PathBuf::from(directory).join(format!("screenshot-{}.png", timestamp));
+        image.save(&path).with_context(|| format!("failed to save screenshot {}", path.display()))?;
+
+        info!("saved screenshot {}", path.display());
+        Ok(path)
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    #[allow(unused)]
+    pub fn scene_mut(&mut self) -> &mut Scene {
+        &mut self.scene
+    }
+
+    /// world space bounds of every mesh in the scene, `None` when there is nothing to draw
+    pub fn scene_bounds(&mut self) -> Option<Bounds> {
+        self.scene.update_world_matrices();
+        self.scene
+            .iter()
+            .filter_map(|(_, node)| Some(self.meshes[node.mesh?].bounds?.transformed(node.world_matrix())))
+            .reduce(|a, b| a.union(&b))
+    }
+
+    /// points the camera at the scene, close enough that it fills the view
+    pub fn frame_object(&mut self) {
+        let Some(bounds) = self.scene_bounds() else {
+            return;
+        };
+        let bounds = &bounds;
+        match (self.draw_config.projection.field_of_view(), &mut self.draw_config.projection) {
+            (Some(field_of_view), _) => self.camera.frame(bounds, field_of_view.vertical(self.draw_config.display_aspect)),
+            // distance doesn't change the size of an orthographic view, only keep the camera outside the mesh
+            (None, Projection::Orthographic { height, .. }) => {
+                *height = bounds.radius() * 2.0;
+                self.camera.frame(bounds, Radians(FRAC_PI_2));
+            }
+            (None, _) => unreachable!("only orthographic projections have no field of view"),
         }
     }
 
+    pub fn set_projection(&mut self, projection: Projection) {
+        info!("projection: {:?}", projection);
+        self.draw_config.projection = projection;
+    }
+
+    /// perspective -> orthographic -> reversed z infinite perspective -> perspective.
+    /// the orthographic view is sized to match what the perspective view shows at the orbit target
+    pub fn cycle_projection(&mut self) {
+        let focus_distance = match &self.camera {
+            Camera::Orbit(orbit) => orbit.distance,
+            Camera::Fly(_) => DEFAULT_FOCUS_DISTANCE,
+        };
+
+        let projection = match self.draw_config.projection {
+            Projection::Perspective {
+                field_of_view,
+                near_clipping_plane,
+                far_clipping_plane,
+            } => Projection::Orthographic {
+                height: 2.0 * focus_distance * (field_of_view.vertical(self.draw_config.display_aspect).0 / 2.0).tan(),
+                near_clipping_plane,
+                far_clipping_plane,
+            },
+            Projection::Orthographic { near_clipping_plane, .. } => Projection::ReversedInfinitePerspective {
+                field_of_view: DEFAULT_FIELD_OF_VIEW,
+                near_clipping_plane,
+            },
+            Projection::ReversedInfinitePerspective { near_clipping_plane, .. } => Projection::Perspective {
+                field_of_view: DEFAULT_FIELD_OF_VIEW,
+                near_clipping_plane,
+                far_clipping_plane: DEFAULT_FAR_CLIPPING_PLANE,
+            },
+        };
+        self.set_projection(projection);
+    }
+
+    /// draw into `render_target` instead of the default framebuffer, it is resized along with the renderer
+    pub fn set_render_target(&mut self, render_target: Option<Framebuffer>) {
+        self.render_target = render_target;
+    }
+
     pub fn resize(&mut self, width: i32, height: i32) {
+        if let Some(framebuffer) = self.render_target.as_mut() {
+            if let Err(e) = framebuffer.resize(width, height) {
+                error!("failed to resize render target: {:?}", e);
+            }
+        }
+
         unsafe {
             self.draw_config.display_dimensions = (width, height);
             self.draw_config.display_aspect = width as f32 / height as f32;
@@ -165,6 +374,11 @@ impl Renderer {
     }
 }
 
+/// the variant of the mesh shaders a material needs
+fn material_program_key(textured: bool) -> ProgramKey {
+    ProgramKey::new(VERTEX_SHADER, FRAGMENT_SHADER).with_feature("TEXTURED", textured)
+}
+
 fn get_gl_string(variant: GLenum) -> Option<&'static CStr> {
     unsafe {
         let s = gl::GetString(variant);
@@ -172,6 +386,24 @@ fn get_gl_string(variant: GLenum) -> Option<&'static CStr> {
     }
 }
 
+/// whether the context is at least GL `major.minor` or exposes `extension`, e.g. "GL_ARB_gl_spirv"
+fn supports(major: GLint, minor: GLint, extension: &str) -> bool {
+    let (mut context_major, mut context_minor, mut extension_count) = (0, 0, 0);
+    unsafe {
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut context_major);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut context_minor);
+        gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut extension_count);
+    }
+    if (context_major, context_minor) >= (major, minor) {
+        return true;
+    }
+
+    (0..extension_count as GLuint).any(|index| unsafe {
+        let name = gl::GetStringi(gl::EXTENSIONS, index);
+        !name.is_null() && CStr::from_ptr(name.cast()).to_bytes() == extension.as_bytes()
+    })
+}
+
 extern "system" fn gl_debug_callback(source: GLenum, er_type: GLenum, id: GLuint, severity: GLenum, _: GLsizei, message: *const GLchar, _: *mut raw::c_void) {
     let message = unsafe { String::from_utf8(CStr::from_ptr(message).to_bytes().to_vec()).unwrap() };
 
@@ -208,3 +440,25 @@ extern "system" fn gl_debug_callback(source: GLenum, er_type: GLenum, id: GLuint
 
     error!("GL ERROR: {:?} [{}] {}: {} {}", id, severity, er_type, source, message);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::layout::GlslBlock;
+    use super::*;
+
+    #[test]
+    fn shaders_match_per_frame_data() {
+        let declaration = PerFrameData::glsl_declaration("uniform", "perFrameData", 0);
+        let header = std::fs::read_to_string(format!("{}/shaders/per_frame_data.glsl", env!("CARGO_MANIFEST_DIR"))).unwrap();
+        assert!(header.contains(&declaration), "per_frame_data.glsl does not declare perFrameData as:\n{}", declaration);
+
+        for shader in [VERTEX_SHADER, FRAGMENT_SHADER] {
+            let source_file = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(shader);
+            for defines in [&[][..], &[("TEXTURED", "")][..]] {
+                let preprocessed = preprocessor::preprocess(&source_file, defines).unwrap();
+                assert_eq!(preprocessed.source.matches("uniform perFrameData").count(), 1, "{} should include per_frame_data.glsl", shader);
+                assert!(preprocessed.source.contains(&declaration), "{} does not declare perFrameData as:\n{}", shader, declaration);
+            }
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+use std::fs;
+
+use super::mesh::{MeshData, MeshGroup, Vertex};
+
+/// loads a Wavefront OBJ file into indexed triangle lists, one `MeshGroup` per `g`/`o` statement.
+/// materials, smoothing groups and free-form geometry are ignored.
+pub fn load(source_file: &str) -> Result<MeshData> {
+    let source = fs::read_to_string(source_file).with_context(|| format!("failed to read obj source file {}", source_file))?;
+    parse(&source).with_context(|| format!("failed to parse obj source file {}", source_file))
+}
+
+/// unit normal of a counter clockwise triangle, zero for degenerate ones
+pub fn flat_normal([p0, p1, p2]: [[f32; 3]; 3]) -> [f32; 3] {
+    let p0 = glm::Vec3::from(p0);
+    let normal = (glm::Vec3::from(p1) - p0).cross(&(glm::Vec3::from(p2) - p0));
+
+    if normal.norm_squared() > 0.0 {
+        normal.normalize().into()
+    } else {
+        [0.0; 3]
+    }
+}
+
+pub fn parse(source: &str) -> Result<MeshData> {
+    let mut parser = ObjParser::default();
+
+    for (line_number, line) in source.lines().enumerate() {
+        parser.parse_line(line).with_context(|| format!("line {}: \"{}\"", line_number + 1, line.trim()))?;
+    }
+
+    parser.close_group();
+    return Ok(parser.data);
+}
+
+// (position, uv, normal) indices of one face corner, already resolved to 0 based
+type Corner = (usize, Option<usize>, Option<usize>);
+
+#[derive(Default)]
+struct ObjParser {
+    positions: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    normals: Vec<[f32; 3]>,
+    vertex_lookup: HashMap<Corner, u32>,
+    group_name: Option<String>,
+    group_start: usize,
+    data: MeshData,
+}
+
+impl ObjParser {
+    fn parse_line(&mut self, line: &str) -> Result<()> {
+        let line = match line.split_once('#') {
+            Some((content, _comment)) => content,
+            None => line,
+        };
+        let mut tokens = line.split_whitespace();
+
+        match tokens.next() {
+            Some("v") => {
+                let [x, y, z] = parse_floats(tokens)?;
+                self.positions.push([x, y, z]);
+            }
+            Some("vt") => {
+                // the optional w component is dropped, v is flipped as images are uploaded top row first
+                let [u, v] = parse_floats(tokens.take(2))?;
+                self.uvs.push([u, 1.0 - v]);
+            }
+            Some("vn") => {
+                let [x, y, z] = parse_floats(tokens)?;
+                self.normals.push([x, y, z]);
+            }
+            Some("f") => {
+                let corners = tokens.map(|token| self.parse_corner(token)).collect::<Result<Vec<_>>>()?;
+                self.add_face(&corners)?;
+            }
+            Some("g") | Some("o") => {
+                self.close_group();
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                self.group_name = Some(name);
+            }
+            // materials, smoothing groups, lines and anything else we don't render
+            Some(_) | None => (),
+        }
+
+        Ok(())
+    }
+
+    fn parse_corner(&self, token: &str) -> Result<Corner> {
+        let mut parts = token.split('/');
+
+        let position = match parts.next() {
+            Some(index) => resolve_index(index, self.positions.len())?,
+            None => return Err(anyhow!("empty face corner")),
+        };
+        let uv = match parts.next() {
+            Some("") | None => None,
+            Some(index) => Some(resolve_index(index, self.uvs.len())?),
+        };
+        let normal = match parts.next() {
+            Some("") | None => None,
+            Some(index) => Some(resolve_index(index, self.normals.len())?),
+        };
+
+        if parts.next().is_some() {
+            return Err(anyhow!("face corner {} has too many components", token));
+        }
+
+        Ok((position, uv, normal))
+    }
+
+    fn add_face(&mut self, corners: &[Corner]) -> Result<()> {
+        if corners.len() < 3 {
+            return Err(anyhow!("face needs at least 3 corners, found {}", corners.len()));
+        }
+
+        let face_normal = self.face_normal(corners);
+
+        let corner_indices: Vec<u32> = corners
+            .iter()
+            .map(|&corner| match corner.2 {
+                Some(_) => self.shared_vertex(corner),
+                None => self.push_vertex(corner, face_normal),
+            })
+            .collect();
+
+        // polygons are assumed convex and triangulated as a fan
+        for i in 1..corner_indices.len() - 1 {
+            self.data.indices.extend_from_slice(&[corner_indices[0], corner_indices[i], corner_indices[i + 1]]);
+        }
+
+        Ok(())
+    }
+
+    // corners with an explicit normal are deduplicated so faces can share vertices
+    fn shared_vertex(&mut self, corner: Corner) -> u32 {
+        if let Some(&index) = self.vertex_lookup.get(&corner) {
+            return index;
+        }
+        let index = self.push_vertex(corner, [0.0; 3]);
+        self.vertex_lookup.insert(corner, index);
+        index
+    }
+
+    fn push_vertex(&mut self, (position, uv, normal): Corner, fallback_normal: [f32; 3]) -> u32 {
+        let index = self.data.vertices.len() as u32;
+        self.data.vertices.push(Vertex {
+            position: self.positions[position],
+            normal: normal.map(|n| self.normals[n]).unwrap_or(fallback_normal),
+            uv: uv.map(|t| self.uvs[t]).unwrap_or_default(),
+        });
+        index
+    }
+
+    fn face_normal(&self, corners: &[Corner]) -> [f32; 3] {
+        flat_normal([self.positions[corners[0].0], self.positions[corners[1].0], self.positions[corners[2].0]])
+    }
+
+    fn close_group(&mut self) {
+        let index_count = self.data.indices.len() - self.group_start;
+        if index_count > 0 {
+            let name = match self.group_name.take() {
+                Some(name) if !name.is_empty() => name,
+                _ => "default".to_string(),
+            };
+            self.data.groups.push(MeshGroup {
+                name,
+                first_index: self.group_start,
+                index_count,
+            });
+        }
+        self.group_start = self.data.indices.len();
+    }
+}
+
+// OBJ indices are 1 based, negative values count back from the most recent element
+fn resolve_index(token: &str, len: usize) -> Result<usize> {
+    let index: i64 = token.parse().with_context(|| format!("invalid index {}", token))?;
+
+    let resolved = match index {
+        i if i > 0 => i - 1,
+        i if i < 0 => len as i64 + i,
+        _ => return Err(anyhow!("index 0 is not valid in obj files")),
+    };
+
+    if resolved < 0 || resolved >= len as i64 {
+        return Err(anyhow!("index {} out of range, {} elements defined", index, len));
+    }
+
+    Ok(resolved as usize)
+}
+
+fn parse_floats<'a, const N: usize>(tokens: impl Iterator<Item = &'a str>) -> Result<[f32; N]> {
+    let values = tokens.map(|token| token.parse::<f32>().with_context(|| format!("invalid number {}", token))).collect::<Result<Vec<_>>>()?;
+
+    // positions may carry an optional w component which we don't use
+    if values.len() < N {
+        return Err(anyhow!("expected {} numbers, found {}", N, values.len()));
+    }
+
+    Ok(values[..N].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_single_triangle() {
+        let data = parse(include_str!("../../../tests/fixtures/obj/triangle.obj")).unwrap();
+
+        assert_eq!(data.indices, vec![0, 1, 2]);
+        assert_eq!(data.vertices.len(), 3);
+        assert_eq!(data.vertices[1].position, [1.0, 0.0, 0.0]);
+        assert_eq!(data.vertices[1].normal, [0.0, 0.0, 1.0]);
+        // v is flipped on load
+        assert_eq!(data.vertices[2].uv, [0.0, 0.0]);
+        assert_eq!(data.vertices[0].uv, [0.0, 1.0]);
+        assert_eq!(
+            data.groups,
+            vec![MeshGroup {
+                name: "default".to_string(),
+                first_index: 0,
+                index_count: 3
+            }]
+        );
+    }
+
+    #[test]
+    fn shares_vertices_between_faces() {
+        let data = parse(include_str!("../../../tests/fixtures/obj/shared.obj")).unwrap();
+
+        assert_eq!(data.vertices.len(), 4);
+        assert_eq!(data.indices, vec![0, 1, 2, 2, 3, 0]);
+    }
+
+    #[test]
+    fn splits_groups_and_triangulates_quads() {
+        let data = parse(include_str!("../../../tests/fixtures/obj/groups.obj")).unwrap();
+
+        let names: Vec<&str> = data.groups.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, vec!["left", "right"]);
+        assert_eq!(data.groups[1].first_index, 6);
+        assert_eq!(data.groups[1].index_count, 6);
+        assert_eq!(data.indices.len(), 12);
+
+        // negative indices resolve against the positions defined so far
+        let right_first = data.vertices[data.indices[6] as usize];
+        assert_eq!(right_first.position, [1.0, 0.0, 0.0]);
+
+        // faces without normals get a flat face normal
+        assert!(data.vertices.iter().all(|v| v.normal == [0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn loads_bundled_cube() {
+        let data = load(concat!(env!("CARGO_MANIFEST_DIR"), "/meshes/cube.obj")).unwrap();
+
+        assert_eq!(data.indices.len(), 36);
+        assert_eq!(data.vertices.len(), 24);
+        assert_eq!(data.groups.len(), 1);
+        assert_eq!(data.groups[0].name, "cube");
+
+        let bounds = data.bounds().unwrap();
+        assert_eq!(bounds.min, glm::vec3(-1.0, -1.0, -1.0));
+        assert_eq!(bounds.max, glm::vec3(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn rejects_out_of_range_index() {
+        let error = parse("v 0 0 0\nv 1 0 0\nf 1 2 3\n").unwrap_err();
+        assert!(format!("{:#}", error).contains("line 3"));
+    }
+
+    #[test]
+    fn rejects_degenerate_face() {
+        assert!(parse("v 0 0 0\nv 1 0 0\nf 1 2\n").is_err());
+    }
+}
@@ -0,0 +1,237 @@
+//! resolves `#include "file"` in GLSL sources and injects `#define`s before they reach the driver.
+//!
+//! core GLSL has no includes and its `#line` directive only takes a source string number, not a
+//! file name. every file read gets a number (its index in `Preprocessed::files`, the root file is 0)
+//! and `#line` directives are emitted around each include, so a compiler log line like `1:4(2): error`
+//! means line 4 of `files[1]`.
+//!
+//! each file is included at most once per shader, so shared headers need no include guards.
+//! it only needs std and anyhow, so tools outside the renderer can share this file.
+
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Preprocessed {
+    pub source: String,
+    /// every file that went into `source`, indexed by `#line` source string number
+    pub files: Vec<PathBuf>,
+}
+
+impl Preprocessed {
+    /// "0: shaders/a.glsl, 1: shaders/b.glsl", for reading compiler logs
+    pub fn file_legend(&self) -> String {
+        self.files.iter().enumerate().map(|(index, file)| format!("{}: {}", index, file.display())).collect::<Vec<_>>().join(", ")
+    }
+}
+
+/// reads `source_file`, following includes relative to the including file's directory.
+/// `defines` become `#define NAME VALUE` lines straight after `#version`
+pub fn preprocess(source_file: &Path, defines: &[(&str, &str)]) -> Result<Preprocessed> {
+    let mut preprocessor = Preprocessor {
+        files: Vec::new(),
+        canonical_files: Vec::new(),
+        stack: Vec::new(),
+        output: String::new(),
+    };
+    preprocessor.include(source_file, defines)?;
+
+    Ok(Preprocessed {
+        source: preprocessor.output,
+        files: preprocessor.files,
+    })
+}
+
+struct Preprocessor {
+    files: Vec<PathBuf>,
+    canonical_files: Vec<PathBuf>,
+    // files currently being expanded, innermost last, to report include cycles
+    stack: Vec<PathBuf>,
+    output: String,
+}
+
+impl Preprocessor {
+    fn include(&mut self, path: &Path, defines: &[(&str, &str)]) -> Result<()> {
+        let canonical = path.canonicalize().with_context(|| format!("failed to find shader source file {}", path.display()))?;
+
+        if let Some(start) = self.stack.iter().position(|file| *file == canonical) {
+            let chain: Vec<String> = self.stack[start..].iter().chain([&canonical]).map(|file| file.display().to_string()).collect();
+            return Err(anyhow!("include cycle: {}", chain.join(" -> ")));
+        }
+        if self.canonical_files.contains(&canonical) {
+            return Ok(());
+        }
+
+        let source = fs::read_to_string(path).with_context(|| format!("failed to read shader source file {}", path.display()))?;
+        let version_line = version_line(&source);
+        let file_number = self.files.len();
+        let is_root = file_number == 0;
+        self.files.push(path.to_path_buf());
+        self.canonical_files.push(canonical.clone());
+        self.stack.push(canonical);
+
+        if !is_root {
+            self.output.push_str(&format!("#line 1 {}\n", file_number));
+        } else if version_line.is_none() && !defines.is_empty() {
+            // without a #version the defines can go first
+            push_defines(&mut self.output, defines);
+            self.output.push_str("#line 1 0\n");
+        }
+
+        let directory = path.parent().unwrap_or(Path::new(""));
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let directive = line.trim_start();
+
+            if directive.starts_with("#version") {
+                if !is_root || version_line != Some(index) {
+                    return Err(anyhow!("{}:{}: #version must come before anything but comments in the root shader", path.display(), line_number));
+                }
+                self.output.push_str(line);
+                self.output.push('\n');
+                push_defines(&mut self.output, defines);
+                self.output.push_str(&format!("#line {} {}\n", line_number + 1, file_number));
+            } else if let Some(argument) = directive.strip_prefix("#include") {
+                let included = parse_include(argument).with_context(|| format!("{}:{}: malformed #include", path.display(), line_number))?;
+                self.include(&directory.join(included), &[]).with_context(|| format!("included from {}:{}", path.display(), line_number))?;
+                self.output.push_str(&format!("#line {} {}\n", line_number + 1, file_number));
+            } else {
+                self.output.push_str(line);
+                self.output.push('\n');
+            }
+        }
+
+        self.stack.pop();
+        Ok(())
+    }
+}
+
+/// index of the `#version` line, if only blank lines and comments come before it
+pub fn version_line(source: &str) -> Option<usize> {
+    let mut in_block_comment = false;
+    for (index, line) in source.lines().enumerate() {
+        let mut rest = line.trim();
+        loop {
+            if in_block_comment {
+                match rest.find("*/") {
+                    Some(end) => rest = rest[end + 2..].trim_start(),
+                    None => break,
+                }
+                in_block_comment = false;
+            }
+            match rest.strip_prefix("/*") {
+                Some(comment) => {
+                    rest = comment;
+                    in_block_comment = true;
+                }
+                None => break,
+            }
+        }
+
+        if in_block_comment || rest.is_empty() || rest.starts_with("//") {
+            continue;
+        }
+        // the directive has to start the line for the expansion loop to find it
+        return (rest.starts_with("#version") && line.trim_start().starts_with("#version")).then_some(index);
+    }
+    None
+}
+
+fn push_defines(output: &mut String, defines: &[(&str, &str)]) {
+    for (name, value) in defines {
+        output.push_str(&format!("#define {} {}\n", name, value));
+    }
+}
+
+// `"file.glsl"`, angle brackets aren't supported as there are no system include paths
+fn parse_include(argument: &str) -> Result<&str> {
+    let argument = argument.trim();
+    match argument.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) {
+        Some(file) if !file.is_empty() => Ok(file),
+        _ => Err(anyhow!("expected #include \"file\", found #include {}", argument)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/glsl").join(name)
+    }
+
+    #[test]
+    fn expands_includes_with_line_directives() {
+        let preprocessed = preprocess(&fixture("main.glsl"), &[("USE_COLOR", "1")]).unwrap();
+
+        let expected = "\
+#version 450 core
+#define USE_COLOR 1
+#line 2 0
+#line 1 1
+// common.glsl
+#line 1 2
+const float PI = 3.14159;
+#line 3 1
+vec3 common_color() { return vec3(PI); }
+#line 3 0
+void main() {}
+";
+        assert_eq!(preprocessed.source, expected);
+        assert_eq!(preprocessed.files.len(), 3);
+        assert!(preprocessed.files[2].ends_with("nested/constants.glsl"));
+    }
+
+    #[test]
+    fn allows_comments_before_version() {
+        let preprocessed = preprocess(&fixture("commented.glsl"), &[("TEXTURED", "")]).unwrap();
+
+        let expected = "\
+// licence header
+
+/* block
+   comment */
+#version 450 core
+#define TEXTURED 
+#line 6 0
+void main() {}
+";
+        assert_eq!(preprocessed.source, expected);
+    }
+
+    #[test]
+    fn finds_the_version_line() {
+        assert_eq!(version_line("#version 450\n"), Some(0));
+        assert_eq!(version_line("\n  // a\n/* b */ /* c\n*/\n #version 450\n"), Some(4));
+        assert_eq!(version_line("/* a */ #version 450\n"), None);
+        assert_eq!(version_line("void main() {}\n#version 450\n"), None);
+        assert_eq!(version_line("/* #version 450 */\n"), None);
+    }
+
+    #[test]
+    fn includes_each_file_once() {
+        let preprocessed = preprocess(&fixture("twice.glsl"), &[]).unwrap();
+
+        assert_eq!(preprocessed.source.matches("const float PI").count(), 1);
+        assert_eq!(preprocessed.files.len(), 3);
+    }
+
+    #[test]
+    fn reports_include_cycles() {
+        let error = preprocess(&fixture("cycle_a.glsl"), &[]).unwrap_err();
+
+        let message = format!("{:#}", error);
+        assert!(message.contains("include cycle"), "{}", message);
+        assert!(message.contains("cycle_a.glsl -> ") && message.contains("cycle_b.glsl -> "), "{}", message);
+    }
+
+    #[test]
+    fn reports_missing_includes_with_location() {
+        let error = preprocess(&fixture("missing.glsl"), &[]).unwrap_err();
+
+        let message = format!("{:#}", error);
+        assert!(message.contains("missing.glsl:3"), "{}", message);
+        assert!(message.contains("does_not_exist.glsl"), "{}", message);
+    }
+}
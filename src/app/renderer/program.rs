@@ -1,59 +1,175 @@
 use anyhow::{anyhow, Context, Result};
 use gl::types::*;
-use log::info;
-use shader::Shader;
+use log::{debug, info, trace};
+use shader::{Shader, ShaderType};
+use std::ffi::c_void;
 use std::fmt;
 
 use super::gl;
+use super::reflection::{self, Reflection, UniformValue};
 use super::shader;
 
 pub struct Program {
     pub handle: u32,
+    pub reflection: Reflection,
 }
 
 impl Program {
+    #[allow(unused)]
     pub fn new(vertex_shader: &Shader, fragment_shader: &Shader) -> Result<Self> {
+        Program::from_stages(&[vertex_shader, fragment_shader])
+    }
+
+    /// links any valid set of stages, see `validate_stages`
+    pub fn from_stages(shaders: &[&Shader]) -> Result<Self> {
+        let stages: Vec<ShaderType> = shaders.iter().map(|shader| shader.shader_type).collect();
+        let names = shaders.iter().map(|shader| shader.to_string()).collect::<Vec<_>>().join(", ");
+        validate_stages(&stages).with_context(|| format!("can't link {}", names))?;
 
         let program_id = unsafe {
             let program_id = gl::CreateProgram();
             if program_id == 0 {
-                return Err(anyhow!("glCreateProgram failed: {}, {}", vertex_shader, fragment_shader));
+                return Err(anyhow!("glCreateProgram failed: {}", names));
             }
 
-            // check Shaders are valid code
-
-            gl::AttachShader(program_id, vertex_shader.handle);
-            gl::AttachShader(program_id, fragment_shader.handle);
+            for shader in shaders {
+                gl::AttachShader(program_id, shader.handle);
+            }
+            // lets `binary` work, drivers may keep extra data around for it
+            gl::ProgramParameteri(program_id, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as GLint);
 
             gl::LinkProgram(program_id);
 
+            // the shaders can be deleted or reused without affecting the linked program
+            for shader in shaders {
+                gl::DetachShader(program_id, shader.handle);
+            }
+
             let mut status = gl::FALSE as GLint;
             gl::GetProgramiv(program_id, gl::LINK_STATUS, &mut status);
 
             if status != (gl::TRUE as GLint) {
-                let mut log_len = 0;
-                gl::GetProgramiv(program_id, gl::INFO_LOG_LENGTH, &mut log_len);
-                let mut log_buf: Vec<u8> = Vec::with_capacity(log_len as usize);
-                log_buf.set_len((log_len as usize) - 1);
-                gl::GetProgramInfoLog(program_id, log_len, std::ptr::null_mut(), log_buf.as_mut_ptr() as *mut GLchar);
-
-                return Err(anyhow!(String::from_utf8(log_buf).unwrap().to_string()))
-                    .context(format!("glLinkPorgram failed {}, {}", vertex_shader, fragment_shader));
+                let log = program_info_log(program_id);
+                gl::DeleteProgram(program_id);
+
+                return Err(anyhow!(log)).context(format!("glLinkProgram failed {}", names));
             }
             program_id
         };
 
-        info!("created program #{} from {}, {}",program_id, vertex_shader, fragment_shader);
+        info!("created program #{} from {}", program_id, names);
+
+        let reflection = unsafe { Reflection::new(program_id) };
+        debug!("program #{} reflection: {:?}", program_id, reflection);
 
-        return Ok(Program { handle: program_id });
+        return Ok(Program { handle: program_id, reflection });
+    }
+
+    /// recreates a program saved with `binary`. fails if the driver rejects it, which it may do after any
+    /// driver update or on a different GPU, in which case the program has to be compiled from source
+    pub fn from_binary(format: GLenum, binary: &[u8]) -> Result<Self> {
+        let program_id = unsafe {
+            let program_id = gl::CreateProgram();
+            if program_id == 0 {
+                return Err(anyhow!("glCreateProgram failed for program binary"));
+            }
+            gl::ProgramBinary(program_id, format, binary.as_ptr() as *const c_void, binary.len() as GLsizei);
+
+            let mut status = gl::FALSE as GLint;
+            gl::GetProgramiv(program_id, gl::LINK_STATUS, &mut status);
+            if status != (gl::TRUE as GLint) {
+                gl::DeleteProgram(program_id);
+                return Err(anyhow!("driver rejected program binary of {} bytes in format {:#x}", binary.len(), format));
+            }
+            program_id
+        };
+
+        info!("created program #{} from binary", program_id);
+
+        let reflection = unsafe { Reflection::new(program_id) };
+        return Ok(Program { handle: program_id, reflection });
+    }
+
+    /// the linked program in the driver's own format, with the format to pass back to `from_binary`
+    pub fn binary(&self) -> Result<(GLenum, Vec<u8>)> {
+        unsafe {
+            let mut length = 0;
+            gl::GetProgramiv(self.handle, gl::PROGRAM_BINARY_LENGTH, &mut length);
+            if length == 0 {
+                return Err(anyhow!("{} has no binary", self));
+            }
+
+            let mut binary = vec![0u8; length as usize];
+            let mut format = 0;
+            let mut written = 0;
+            gl::GetProgramBinary(self.handle, length, &mut written, &mut format, binary.as_mut_ptr() as *mut c_void);
+            binary.truncate(written as usize);
+            Ok((format, binary))
+        }
+    }
+
+    /// sets a uniform outside any block by name, checking it's active and `value` matches its type.
+    /// doesn't need the program to be in use
+    #[allow(unused)]
+    pub fn set_uniform<T: UniformValue>(&self, name: &str, value: T) -> Result<()> {
+        let uniform = self
+            .reflection
+            .uniforms
+            .get(name)
+            .ok_or_else(|| anyhow!("{} has no active uniform {}, it may have been optimised out", self, name))?;
+        if !T::GL_TYPES.contains(&uniform.gl_type) {
+            return Err(anyhow!("uniform {} of {} is a {}, can't set it from {}", name, self, reflection::type_name(uniform.gl_type), std::any::type_name::<T>()));
+        }
+
+        unsafe {
+            value.set(self.handle, uniform.location);
+        }
+        Ok(())
     }
 
     pub unsafe fn use_program(&self) {
-        info!("using program: {}", self);
+        trace!("using program: {}", self);
         gl::UseProgram(self.handle);
     }
 }
 
+/// checks a set of stages makes a program: either a lone compute shader, or a vertex shader with
+/// optional tessellation, geometry and fragment stages. tessellation control needs an evaluation stage
+/// to feed, evaluation on its own uses the patch size set with glPatchParameteri
+pub fn validate_stages(stages: &[ShaderType]) -> Result<()> {
+    for (index, stage) in stages.iter().enumerate() {
+        if stages[..index].contains(stage) {
+            return Err(anyhow!("more than one {} shader", stage.name()));
+        }
+    }
+
+    if stages.contains(&ShaderType::COMPUTE) {
+        return match stages.len() {
+            1 => Ok(()),
+            _ => Err(anyhow!("a compute shader can't be linked with other stages")),
+        };
+    }
+    if !stages.contains(&ShaderType::VERTEX) {
+        return Err(anyhow!("a graphics program needs a vertex shader"));
+    }
+    if stages.contains(&ShaderType::TESS_CONTROL) && !stages.contains(&ShaderType::TESS_EVALUATION) {
+        return Err(anyhow!("a tessellation control shader needs a tessellation evaluation shader"));
+    }
+    Ok(())
+}
+
+unsafe fn program_info_log(program: GLuint) -> String {
+    let mut log_len = 0;
+    gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut log_len);
+    if log_len == 0 {
+        return String::from("no info log");
+    }
+    let mut log_buf = vec![0u8; log_len as usize];
+    let mut written = 0;
+    gl::GetProgramInfoLog(program, log_len, &mut written, log_buf.as_mut_ptr() as *mut GLchar);
+    String::from_utf8_lossy(&log_buf[..written as usize]).into_owned()
+}
+
 impl Drop for Program {
     fn drop(&mut self) {
         info!("deleting {}", self);
@@ -68,3 +184,58 @@ impl fmt::Display for Program {
         write!(f, "program #{}", self.handle)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::headless::gl_test_context;
+    use crate::app::renderer::shader::stage_fixture;
+    use ShaderType::*;
+
+    #[test]
+    fn accepts_graphics_and_compute_stage_sets() {
+        assert!(validate_stages(&[VERTEX, FRAGMENT]).is_ok());
+        assert!(validate_stages(&[VERTEX]).is_ok());
+        assert!(validate_stages(&[FRAGMENT, GEOMETRY, VERTEX]).is_ok());
+        assert!(validate_stages(&[VERTEX, TESS_CONTROL, TESS_EVALUATION, GEOMETRY, FRAGMENT]).is_ok());
+        assert!(validate_stages(&[VERTEX, TESS_EVALUATION, FRAGMENT]).is_ok());
+        assert!(validate_stages(&[COMPUTE]).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_stage_sets() {
+        let message = |stages: &[ShaderType]| validate_stages(stages).unwrap_err().to_string();
+
+        assert!(message(&[]).contains("needs a vertex shader"));
+        assert!(message(&[FRAGMENT]).contains("needs a vertex shader"));
+        assert!(message(&[VERTEX, VERTEX, FRAGMENT]).contains("more than one vertex shader"));
+        assert!(message(&[COMPUTE, VERTEX]).contains("compute shader can't be linked"));
+        assert!(message(&[VERTEX, TESS_CONTROL, FRAGMENT]).contains("needs a tessellation evaluation shader"));
+    }
+
+    #[test]
+    fn links_tessellation_and_geometry_stages() {
+        let _context = gl_test_context();
+
+        let vertex = stage_fixture(ShaderType::VERTEX, "vertex");
+        let tess_control = stage_fixture(ShaderType::TESS_CONTROL, "tess_control");
+        let tess_evaluation = stage_fixture(ShaderType::TESS_EVALUATION, "tess_evaluation");
+        let geometry = stage_fixture(ShaderType::GEOMETRY, "geometry");
+        let fragment = stage_fixture(ShaderType::FRAGMENT, "fragment");
+
+        Program::from_stages(&[&vertex, &tess_control, &tess_evaluation, &geometry, &fragment]).unwrap();
+        Program::from_stages(&[&vertex, &geometry, &fragment]).unwrap();
+        assert!(Program::from_stages(&[&vertex, &tess_control, &fragment]).is_err());
+    }
+
+    #[test]
+    fn link_failures_report_the_log() {
+        let _context = gl_test_context();
+
+        let vertex = stage_fixture(ShaderType::VERTEX, "vertex");
+        let fragment = stage_fixture(ShaderType::FRAGMENT, "unmatched_fragment");
+        let error = Program::from_stages(&[&vertex, &fragment]).err().unwrap();
+        assert!(format!("{:#}", error).contains("glLinkProgram failed"), "{:#}", error);
+        assert!(format!("{:#}", error).contains("color"), "{:#}", error);
+    }
+}
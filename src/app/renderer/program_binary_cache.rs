@@ -0,0 +1,239 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, warn};
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use super::gl;
+use super::get_gl_string;
+use super::program::Program;
+use super::shader::ShaderType;
+
+/// linked programs saved to disk with glGetProgramBinary, so a launch with unchanged shaders skips compiling.
+/// files are named by a hash of the preprocessed sources and the GL renderer and version, so editing a
+/// shader or updating the driver misses the cache instead of loading something stale. a binary the driver
+/// still rejects is deleted and the program compiled from source. every edit leaves a binary behind, so the
+/// directory is kept to the `max_programs` most recently used ones
+pub struct ProgramBinaryCache {
+    directory: PathBuf,
+    driver: String,
+    max_programs: usize,
+}
+
+const MAX_PROGRAMS: usize = 64;
+
+impl ProgramBinaryCache {
+    /// `None` when the driver has no program binary formats, a current GL context is needed
+    pub fn new(directory: impl Into<PathBuf>) -> Option<Self> {
+        let mut formats = 0;
+        unsafe {
+            gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut formats);
+        }
+        if formats == 0 {
+            info!("driver has no program binary formats, programs will be compiled on every launch");
+            return None;
+        }
+
+        let driver_string = |name| get_gl_string(name).map_or(String::new(), |string| string.to_string_lossy().into_owned());
+        Some(ProgramBinaryCache {
+            directory: directory.into(),
+            driver: format!("{}\0{}", driver_string(gl::RENDERER), driver_string(gl::VERSION)),
+            max_programs: MAX_PROGRAMS,
+        })
+    }
+
+    /// identifies a program built from `stages`, each a stage type and its preprocessed source
+    pub fn key<'a>(&self, stages: impl IntoIterator<Item = (ShaderType, &'a str)>) -> u64 {
+        let mut hash = Fnv1a::new();
+        hash.write(self.driver.as_bytes());
+        for (shader_type, source) in stages {
+            hash.write(&[0]);
+            hash.write(&(shader_type as u32).to_le_bytes());
+            hash.write(source.as_bytes());
+        }
+        hash.finish()
+    }
+
+    fn path(&self, key: u64) -> PathBuf {
+        self.directory.join(format!("{:016x}.bin", key))
+    }
+
+    /// the program saved under `key`, `None` if there isn't one or the driver won't load it
+    pub fn load(&self, key: u64) -> Option<Program> {
+        let path = self.path(key);
+        let contents = fs::read(&path).ok()?;
+
+        let program = match contents.split_first_chunk::<4>() {
+            Some((format, binary)) => Program::from_binary(u32::from_le_bytes(*format), binary),
+            None => Err(anyhow!("file is too short")),
+        };
+        match program {
+            Ok(program) => {
+                debug!("loaded {} from {}", program, path.display());
+                // the modification time marks when a binary was last used, for `prune`
+                if let Err(e) = fs::File::options().append(true).open(&path).and_then(|file| file.set_modified(SystemTime::now())) {
+                    warn!("failed to touch {}: {}", path.display(), e);
+                }
+                Some(program)
+            }
+            Err(e) => {
+                warn!("discarding cached program {}: {:?}", path.display(), e);
+                if let Err(e) = fs::remove_file(&path) {
+                    warn!("failed to remove {}: {}", path.display(), e);
+                }
+                None
+            }
+        }
+    }
+
+    /// saves `program` under `key`, as a little endian u32 binary format followed by the binary
+    pub fn store(&self, key: u64, program: &Program) -> Result<()> {
+        let (format, binary) = program.binary()?;
+        let mut contents = Vec::with_capacity(4 + binary.len());
+        contents.extend_from_slice(&format.to_le_bytes());
+        contents.extend_from_slice(&binary);
+
+        fs::create_dir_all(&self.directory).with_context(|| format!("failed to create program cache directory {}", self.directory.display()))?;
+        // written under another name first, so another instance never loads half a file
+        let path = self.path(key);
+        let partial_path = path.with_extension(format!("{}.partial", std::process::id()));
+        fs::write(&partial_path, contents).with_context(|| format!("failed to write {}", partial_path.display()))?;
+        fs::rename(&partial_path, &path).with_context(|| format!("failed to move {} to {}", partial_path.display(), path.display()))?;
+
+        debug!("saved {} to {}", program, path.display());
+        self.prune()
+    }
+
+    /// deletes the least recently used binaries beyond `max_programs`
+    fn prune(&self) -> Result<()> {
+        let entries = fs::read_dir(&self.directory).with_context(|| format!("failed to list {}", self.directory.display()))?;
+        let mut binaries = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "bin") {
+                let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).unwrap_or(SystemTime::UNIX_EPOCH);
+                binaries.push((modified, path));
+            }
+        }
+
+        binaries.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+        for (_, path) in binaries.iter().skip(self.max_programs) {
+            info!("removing unused program binary {}", path.display());
+            fs::remove_file(path).with_context(|| format!("failed to remove {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+/// 64 bit FNV-1a, small and stable across Rust versions unlike `DefaultHasher`
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(0xcbf29ce484222325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::headless::gl_test_context;
+    use crate::app::renderer::program_cache::{ProgramCache, ProgramKey};
+    use crate::app::renderer::shader::stage_fixture;
+    use std::time::Duration;
+
+    fn fnv1a(bytes: &[u8]) -> u64 {
+        let mut hash = Fnv1a::new();
+        hash.write(bytes);
+        hash.finish()
+    }
+
+    #[test]
+    fn fnv1a_matches_reference_values() {
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn key_covers_driver_stages_and_sources() {
+        let cache = |driver: &str| ProgramBinaryCache {
+            directory: PathBuf::new(),
+            driver: driver.to_string(),
+            max_programs: MAX_PROGRAMS,
+        };
+        let stages = [(ShaderType::VERTEX, "void main() {}"), (ShaderType::FRAGMENT, "void main() {}")];
+        let key = cache("llvmpipe\x004.5").key(stages);
+
+        assert_eq!(cache("llvmpipe\x004.5").key(stages), key);
+        assert_ne!(cache("llvmpipe\x004.6").key(stages), key);
+        assert_ne!(cache("llvmpipe\x004.5").key([(ShaderType::VERTEX, "void main() {}")]), key);
+        assert_ne!(cache("llvmpipe\x004.5").key([stages[1], stages[0]]), key);
+        assert_ne!(cache("llvmpipe\x004.5").key([stages[0], (ShaderType::FRAGMENT, "void main() { }")]), key);
+    }
+
+    #[test]
+    fn prune_keeps_the_most_recently_used_binaries() {
+        let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/program_binary_prune");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let cache = ProgramBinaryCache {
+            directory: directory.clone(),
+            driver: String::new(),
+            max_programs: 2,
+        };
+
+        for key in 0..4u64 {
+            let file = fs::File::create(cache.path(key)).unwrap();
+            file.set_modified(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(key)).unwrap();
+        }
+        fs::write(directory.join("notes.txt"), "").unwrap();
+        cache.prune().unwrap();
+
+        let mut remaining: Vec<_> = fs::read_dir(&directory).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        remaining.sort();
+        assert_eq!(remaining, ["0000000000000002.bin", "0000000000000003.bin", "notes.txt"]);
+    }
+
+    #[test]
+    fn program_binaries_round_trip_and_bad_ones_are_discarded() {
+        let _context = gl_test_context();
+        let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/program_binary_cache");
+        let _ = fs::remove_dir_all(&directory);
+        let Some(binary_cache) = ProgramBinaryCache::new(&directory) else {
+            warn!("driver has no program binary formats, skipping");
+            return;
+        };
+
+        let program = Program::new(&stage_fixture(ShaderType::VERTEX, "vertex"), &stage_fixture(ShaderType::FRAGMENT, "fragment")).unwrap();
+        binary_cache.store(42, &program).unwrap();
+        let loaded = binary_cache.load(42).unwrap();
+        assert_eq!(loaded.reflection, program.reflection);
+        assert!(binary_cache.load(7).is_none());
+
+        let path = directory.join(format!("{:016x}.bin", 42));
+        fs::write(&path, b"\0\0\0\0not a program").unwrap();
+        assert!(binary_cache.load(42).is_none());
+        assert!(!path.exists(), "rejected binaries are deleted");
+
+        // a second cache over the same directory finds what the first compiled
+        let textured = ProgramKey::new("shaders/vertex.glsl", "shaders/fragment.glsl").with_feature("TEXTURED", true);
+        let mut programs = ProgramCache::new(Duration::ZERO, ProgramBinaryCache::new(&directory));
+        let compiled = programs.get(&textured).unwrap().reflection.clone();
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 1);
+        let mut programs = ProgramCache::new(Duration::ZERO, ProgramBinaryCache::new(&directory));
+        assert_eq!(programs.get(&textured).unwrap().reflection, compiled);
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,233 @@
+use anyhow::{anyhow, Result};
+use log::{error, info, warn};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::preprocessor;
+use super::program::Program;
+use super::program_binary_cache::ProgramBinaryCache;
+use super::shader::{Shader, ShaderType};
+use super::shader_watcher::ShaderWatcher;
+
+/// one variant of a program: the sources it's built from and the defines they're compiled with.
+/// defines are kept sorted, so the order they were added in doesn't make a different variant
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ProgramKey {
+    pub vertex_source: String,
+    pub fragment_source: String,
+    pub defines: BTreeMap<String, String>,
+}
+
+impl ProgramKey {
+    pub fn new(vertex_source: &str, fragment_source: &str) -> Self {
+        ProgramKey {
+            vertex_source: vertex_source.to_string(),
+            fragment_source: fragment_source.to_string(),
+            defines: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_define(mut self, name: &str, value: &str) -> Self {
+        self.defines.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// `#define NAME` with an empty value, for features tested with `#ifdef`
+    pub fn with_feature(self, name: &str, enabled: bool) -> Self {
+        match enabled {
+            true => self.with_define(name, ""),
+            false => self,
+        }
+    }
+
+    fn defines(&self) -> Vec<(&str, &str)> {
+        self.defines.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect()
+    }
+}
+
+struct CachedProgram {
+    program: Program,
+    /// every file the shaders were preprocessed from
+    source_files: Vec<PathBuf>,
+}
+
+impl CachedProgram {
+    /// links the variant, loading it from `binary_cache` when the preprocessed sources are unchanged
+    fn compile(key: &ProgramKey, binary_cache: Option<&ProgramBinaryCache>) -> Result<Self> {
+        let defines = key.defines();
+        let stages = [(ShaderType::VERTEX, &key.vertex_source), (ShaderType::FRAGMENT, &key.fragment_source)];
+        let mut preprocessed = Vec::with_capacity(stages.len());
+        for (shader_type, source_file) in stages {
+            preprocessed.push((shader_type, source_file, preprocessor::preprocess(Path::new(source_file), &defines)?));
+        }
+        let source_files = preprocessed.iter().flat_map(|(_, _, preprocessed)| preprocessed.files.iter().cloned()).collect();
+
+        let binary_key = binary_cache.map(|cache| cache.key(preprocessed.iter().map(|(shader_type, _, preprocessed)| (*shader_type, preprocessed.source.as_str()))));
+        if let Some(program) = binary_cache.zip(binary_key).and_then(|(cache, binary_key)| cache.load(binary_key)) {
+            return Ok(CachedProgram { program, source_files });
+        }
+
+        let mut shaders = Vec::with_capacity(preprocessed.len());
+        for (shader_type, source_file, preprocessed) in preprocessed {
+            shaders.push(Shader::from_preprocessed(shader_type, source_file, &defines, preprocessed)?);
+        }
+        let program = Program::from_stages(&shaders.iter().collect::<Vec<_>>())?;
+
+        if let Some((cache, binary_key)) = binary_cache.zip(binary_key) {
+            if let Err(e) = cache.store(binary_key, &program) {
+                warn!("failed to cache {}: {:?}", program, e);
+            }
+        }
+        Ok(CachedProgram { program, source_files })
+    }
+}
+
+/// compiles program variants the first time they're asked for and hands out the same program after that.
+/// also watches every source file it has compiled and rebuilds the variants that use an edited file
+pub struct ProgramCache {
+    // `None` marks a variant that failed to compile, so it isn't retried every frame until its sources change
+    programs: HashMap<ProgramKey, Option<CachedProgram>>,
+    watcher: ShaderWatcher,
+    binary_cache: Option<ProgramBinaryCache>,
+}
+
+impl ProgramCache {
+    pub fn new(poll_interval: Duration, binary_cache: Option<ProgramBinaryCache>) -> Self {
+        ProgramCache {
+            programs: HashMap::new(),
+            watcher: ShaderWatcher::new(poll_interval),
+            binary_cache,
+        }
+    }
+
+    pub fn get(&mut self, key: &ProgramKey) -> Result<&Program> {
+        if !self.programs.contains_key(key) {
+            let compiled = match CachedProgram::compile(key, self.binary_cache.as_ref()) {
+                Ok(compiled) => {
+                    info!("compiled variant {:?} as {}", key.defines, compiled.program);
+                    Some(compiled)
+                }
+                Err(e) => {
+                    error!("failed to compile {:?}: {:?}", key, e);
+                    None
+                }
+            };
+            self.programs.insert(key.clone(), compiled);
+            for source_file in [&key.vertex_source, &key.fragment_source] {
+                self.watcher.watch(source_file);
+            }
+            if let Some(compiled) = self.programs[key].as_ref() {
+                for source_file in &compiled.source_files {
+                    self.watcher.watch(source_file);
+                }
+            }
+        }
+
+        match &self.programs[key] {
+            Some(compiled) => Ok(&compiled.program),
+            None => Err(anyhow!("{:?} failed to compile, fix its sources to retry", key)),
+        }
+    }
+
+    #[allow(unused)]
+    pub fn len(&self) -> usize {
+        self.programs.len()
+    }
+
+    #[allow(unused)]
+    pub fn is_empty(&self) -> bool {
+        self.programs.is_empty()
+    }
+
+    /// recompiles the variants built from any source file saved since the last call. a variant that
+    /// fails keeps its previous program and the compiler log is printed
+    pub fn reload_changed(&mut self) {
+        let changed = self.watcher.poll();
+        if changed.is_empty() {
+            return;
+        }
+        info!("shader sources changed: {:?}", changed);
+
+        let mut new_sources = Vec::new();
+        for (key, cached) in self.programs.iter_mut() {
+            let affected = match cached {
+                Some(compiled) => compiled.source_files.iter().any(|file| changed.contains(file)),
+                // a broken variant may have failed on an include we never saw, retry it on any change
+                None => true,
+            };
+            if !affected {
+                continue;
+            }
+
+            match CachedProgram::compile(key, self.binary_cache.as_ref()) {
+                Ok(compiled) => {
+                    info!("reloaded {:?}, now using {}", key.defines, compiled.program);
+                    new_sources.extend(compiled.source_files.iter().cloned());
+                    *cached = Some(compiled);
+                }
+                Err(e) => match cached {
+                    Some(old) => error!("shader reload failed, keeping {}: {:?}", old.program, e),
+                    None => error!("failed to compile {:?}: {:?}", key, e),
+                },
+            }
+        }
+
+        // the edit may have added includes
+        for source_file in new_sources {
+            self.watcher.watch(source_file);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::headless::gl_test_context;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    fn hash(key: &ProgramKey) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn define_order_does_not_matter() {
+        let a = ProgramKey::new("v.glsl", "f.glsl").with_define("A", "1").with_feature("TEXTURED", true);
+        let b = ProgramKey::new("v.glsl", "f.glsl").with_feature("TEXTURED", true).with_define("A", "1");
+
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+        assert_eq!(a.defines(), vec![("A", "1"), ("TEXTURED", "")]);
+    }
+
+    #[test]
+    fn disabled_features_are_left_out() {
+        let plain = ProgramKey::new("v.glsl", "f.glsl");
+
+        assert_eq!(plain.clone().with_feature("TEXTURED", false), plain);
+        assert_ne!(plain.clone().with_feature("TEXTURED", true), plain);
+        assert_ne!(ProgramKey::new("v.glsl", "other.glsl"), plain);
+    }
+
+    #[test]
+    fn program_variants_are_compiled_once() {
+        let _context = gl_test_context();
+        let mut programs = ProgramCache::new(Duration::ZERO, None);
+
+        let textured = ProgramKey::new("shaders/vertex.glsl", "shaders/fragment.glsl").with_feature("TEXTURED", true);
+        let handle = programs.get(&textured).unwrap().handle;
+        assert_eq!(programs.len(), 1);
+
+        let wireframe = textured.clone().with_define("WIREFRAME", "1");
+        assert_ne!(programs.get(&wireframe).unwrap().handle, handle);
+        assert_eq!(programs.get(&textured).unwrap().handle, handle);
+        assert_eq!(programs.len(), 2);
+
+        let broken = ProgramKey::new("shaders/vertex.glsl", "shaders/does_not_exist.glsl");
+        assert!(programs.get(&broken).is_err());
+        assert!(programs.get(&broken).is_err());
+    }
+}
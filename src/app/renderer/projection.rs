@@ -0,0 +1,154 @@
+use super::gl;
+use super::intrinsics::FieldOfView;
+
+/// how view space is mapped onto the screen. every variant takes the display aspect (width / height)
+/// when building its matrix and keeps the vertical extent fixed, so resizing only changes how much is
+/// visible horizontally
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    Perspective {
+        field_of_view: FieldOfView,
+        near_clipping_plane: f32,
+        far_clipping_plane: f32,
+    },
+    /// parallel projection for top/front/side views, `height` is the world space extent shown vertically
+    Orthographic {
+        height: f32,
+        near_clipping_plane: f32,
+        far_clipping_plane: f32,
+    },
+    /// perspective without a far plane, depth goes from 1 at the near plane towards 0 at infinity.
+    /// floats are densest near 0, so precision ends up spread evenly over distance instead of being
+    /// spent right in front of the camera
+    ReversedInfinitePerspective { field_of_view: FieldOfView, near_clipping_plane: f32 },
+}
+
+impl Projection {
+    pub fn matrix(&self, display_aspect: f32) -> glm::Mat4 {
+        match *self {
+            Projection::Perspective {
+                field_of_view,
+                near_clipping_plane,
+                far_clipping_plane,
+            } => glm::perspective(display_aspect, field_of_view.vertical(display_aspect).0, near_clipping_plane, far_clipping_plane),
+            Projection::Orthographic {
+                height,
+                near_clipping_plane,
+                far_clipping_plane,
+            } => {
+                let half_height = height / 2.0;
+                let half_width = half_height * display_aspect;
+                glm::ortho(-half_width, half_width, -half_height, half_height, near_clipping_plane, far_clipping_plane)
+            }
+            Projection::ReversedInfinitePerspective {
+                field_of_view,
+                near_clipping_plane,
+            } => glm::reversed_infinite_perspective_rh_zo(display_aspect, field_of_view.vertical(display_aspect).0, near_clipping_plane),
+        }
+    }
+
+    pub fn is_reversed_z(&self) -> bool {
+        matches!(self, Projection::ReversedInfinitePerspective { .. })
+    }
+
+    /// `None` for orthographic projections
+    pub fn field_of_view(&self) -> Option<FieldOfView> {
+        match *self {
+            Projection::Perspective { field_of_view, .. } | Projection::ReversedInfinitePerspective { field_of_view, .. } => Some(field_of_view),
+            Projection::Orthographic { .. } => None,
+        }
+    }
+
+    /// sets the clip space depth range, depth test and clear depth to suit this projection.
+    /// reversed z needs a [0, 1] depth range, with GL's default [-1, 1] half the precision is lost
+    /// mapping back into the depth buffer
+    pub unsafe fn apply_depth_state(&self) {
+        if self.is_reversed_z() {
+            gl::ClipControl(gl::LOWER_LEFT, gl::ZERO_TO_ONE);
+            gl::DepthFunc(gl::GREATER);
+            gl::ClearDepth(0.0);
+        } else {
+            gl::ClipControl(gl::LOWER_LEFT, gl::NEGATIVE_ONE_TO_ONE);
+            gl::DepthFunc(gl::LESS);
+            gl::ClearDepth(1.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::renderer::intrinsics::Degrees;
+
+    fn project(projection: &Projection, aspect: f32, point: glm::Vec3) -> glm::Vec3 {
+        let clip = projection.matrix(aspect) * glm::vec4(point.x, point.y, point.z, 1.0);
+        clip.xyz() / clip.w
+    }
+
+    fn assert_near(a: &glm::Vec3, b: &glm::Vec3) {
+        assert!(glm::distance(a, b) < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn orthographic_keeps_height_and_widens_with_aspect() {
+        let projection = Projection::Orthographic {
+            height: 4.0,
+            near_clipping_plane: 0.1,
+            far_clipping_plane: 100.0,
+        };
+
+        // top right corner of the view volume, at any depth
+        assert_near(&project(&projection, 2.0, glm::vec3(4.0, 2.0, -0.1)), &glm::vec3(1.0, 1.0, -1.0));
+        assert_near(&project(&projection, 2.0, glm::vec3(4.0, 2.0, -100.0)), &glm::vec3(1.0, 1.0, 1.0));
+        assert_near(&project(&projection, 0.5, glm::vec3(1.0, 2.0, -50.0)).xy().push(0.0), &glm::vec3(1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn reversed_z_maps_near_to_one_and_infinity_towards_zero() {
+        let projection = Projection::ReversedInfinitePerspective {
+            field_of_view: FieldOfView::vertical_degrees(90.0),
+            near_clipping_plane: 0.5,
+        };
+        assert!(projection.is_reversed_z());
+
+        assert!((project(&projection, 1.0, glm::vec3(0.0, 0.0, -0.5)).z - 1.0).abs() < 1e-6);
+        let far = project(&projection, 1.0, glm::vec3(0.0, 0.0, -1.0e6)).z;
+        assert!(far > 0.0 && far < 1e-6);
+
+        // x extent follows the aspect like a regular perspective projection
+        assert_near(&project(&projection, 2.0, glm::vec3(2.0, 1.0, -1.0)).xy().push(0.0), &glm::vec3(1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn perspective_matches_known_matrix() {
+        let projection = Projection::Perspective {
+            field_of_view: FieldOfView::vertical_degrees(90.0),
+            near_clipping_plane: 1.0,
+            far_clipping_plane: 3.0,
+        };
+
+        // the OpenGL perspective matrix for a 90 degree vertical view on a 2:1 display, n = 1, f = 3:
+        // x scale 1 / (aspect tan(fov / 2)), y scale 1 / tan(fov / 2), z -(f + n) / (f - n), w -2fn / (f - n)
+        #[rustfmt::skip]
+        let expected = glm::mat4(
+            0.5, 0.0,  0.0,  0.0,
+            0.0, 1.0,  0.0,  0.0,
+            0.0, 0.0, -2.0, -3.0,
+            0.0, 0.0, -1.0,  0.0,
+        );
+        assert!(glm::abs(&(projection.matrix(2.0) - expected)).max() < 1e-6, "{}", projection.matrix(2.0));
+    }
+
+    #[test]
+    fn horizontal_field_of_view_fixes_the_width() {
+        let projection = Projection::Perspective {
+            field_of_view: FieldOfView::Horizontal(Degrees(90.0).into()),
+            near_clipping_plane: 1.0,
+            far_clipping_plane: 3.0,
+        };
+
+        let matrix = projection.matrix(2.0);
+        assert!((matrix[(0, 0)] - 1.0).abs() < 1e-6);
+        assert!((matrix[(1, 1)] - 2.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,349 @@
+use anyhow::{anyhow, Result};
+use gl::types::*;
+use std::collections::BTreeMap;
+
+use super::gl;
+
+/// a uniform outside any block, set with `Program::set_uniform`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Uniform {
+    pub location: GLint,
+    pub gl_type: GLenum,
+    /// 1 for non arrays
+    pub array_size: GLint,
+}
+
+/// a member of a uniform or shader storage block
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockMember {
+    pub gl_type: GLenum,
+    /// bytes from the start of the block
+    pub offset: GLint,
+    /// 1 for non arrays, 0 for the unsized array at the end of a storage block
+    pub array_size: GLint,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+    pub binding: GLint,
+    /// minimum size of a buffer bound to the block, for storage blocks ending in an unsized array
+    /// it counts one element of the array
+    pub data_size: usize,
+    pub members: BTreeMap<String, BlockMember>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attribute {
+    pub location: GLint,
+    pub gl_type: GLenum,
+}
+
+/// everything active in a linked program, as reported by the driver. anything the compiler found unused
+/// is optimised out and won't be listed. array names are stored without their `[0]` suffix
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Reflection {
+    pub uniforms: BTreeMap<String, Uniform>,
+    pub uniform_blocks: BTreeMap<String, Block>,
+    pub storage_blocks: BTreeMap<String, Block>,
+    pub attributes: BTreeMap<String, Attribute>,
+}
+
+impl Reflection {
+    /// `program` must have linked successfully
+    pub unsafe fn new(program: GLuint) -> Self {
+        let uniform_blocks = reflect_blocks(program, gl::UNIFORM_BLOCK);
+        let storage_blocks = reflect_blocks(program, gl::SHADER_STORAGE_BLOCK);
+        // members refer to their block by resource index
+        let uniform_block_names: Vec<String> = uniform_blocks.iter().map(|(name, _)| name.clone()).collect();
+        let storage_block_names: Vec<String> = storage_blocks.iter().map(|(name, _)| name.clone()).collect();
+        let mut reflection = Reflection {
+            uniform_blocks: uniform_blocks.into_iter().collect(),
+            storage_blocks: storage_blocks.into_iter().collect(),
+            ..Default::default()
+        };
+
+        for index in 0..resource_count(program, gl::UNIFORM) {
+            let name = resource_name(program, gl::UNIFORM, index);
+            let [gl_type, array_size, location, block_index, offset] =
+                resource_properties(program, gl::UNIFORM, index, [gl::TYPE, gl::ARRAY_SIZE, gl::LOCATION, gl::BLOCK_INDEX, gl::OFFSET]);
+
+            match block_index {
+                -1 => {
+                    reflection.uniforms.insert(
+                        name,
+                        Uniform {
+                            location,
+                            gl_type: gl_type as GLenum,
+                            array_size,
+                        },
+                    );
+                }
+                block_index => {
+                    let block_name = &uniform_block_names[block_index as usize];
+                    let member = BlockMember {
+                        gl_type: gl_type as GLenum,
+                        offset,
+                        array_size,
+                    };
+                    reflection.uniform_blocks.get_mut(block_name).unwrap().members.insert(name, member);
+                }
+            }
+        }
+
+        for index in 0..resource_count(program, gl::BUFFER_VARIABLE) {
+            let name = resource_name(program, gl::BUFFER_VARIABLE, index);
+            let [gl_type, array_size, block_index, offset] = resource_properties(program, gl::BUFFER_VARIABLE, index, [gl::TYPE, gl::ARRAY_SIZE, gl::BLOCK_INDEX, gl::OFFSET]);
+            let block_name = &storage_block_names[block_index as usize];
+            let member = BlockMember {
+                gl_type: gl_type as GLenum,
+                offset,
+                array_size,
+            };
+            reflection.storage_blocks.get_mut(block_name).unwrap().members.insert(name, member);
+        }
+
+        for index in 0..resource_count(program, gl::PROGRAM_INPUT) {
+            let name = resource_name(program, gl::PROGRAM_INPUT, index);
+            let [gl_type, location] = resource_properties(program, gl::PROGRAM_INPUT, index, [gl::TYPE, gl::LOCATION]);
+            // built ins like gl_VertexID have no location
+            if location >= 0 {
+                reflection.attributes.insert(
+                    name,
+                    Attribute {
+                        location,
+                        gl_type: gl_type as GLenum,
+                    },
+                );
+            }
+        }
+
+        reflection
+    }
+
+    /// checks the Rust side of a uniform block is the size the shader expects, `size` is usually `size_of::<T>()`.
+    /// std140 rounds a block up to 16 bytes but drivers may report the size before rounding
+    pub fn check_uniform_block_size(&self, name: &str, size: usize) -> Result<()> {
+        let block = self.uniform_blocks.get(name).ok_or_else(|| anyhow!("no active uniform block {}", name))?;
+        if block.data_size.next_multiple_of(16) != size {
+            return Err(anyhow!("uniform block {} is {} bytes in the shader but {} bytes in Rust", name, block.data_size, size));
+        }
+        Ok(())
+    }
+}
+
+unsafe fn reflect_blocks(program: GLuint, interface: GLenum) -> Vec<(String, Block)> {
+    (0..resource_count(program, interface))
+        .map(|index| {
+            let name = resource_name(program, interface, index);
+            let [binding, data_size] = resource_properties(program, interface, index, [gl::BUFFER_BINDING, gl::BUFFER_DATA_SIZE]);
+            let block = Block {
+                binding,
+                data_size: data_size as usize,
+                members: BTreeMap::new(),
+            };
+            (name, block)
+        })
+        .collect()
+}
+
+unsafe fn resource_count(program: GLuint, interface: GLenum) -> GLuint {
+    let mut count = 0;
+    gl::GetProgramInterfaceiv(program, interface, gl::ACTIVE_RESOURCES, &mut count);
+    count as GLuint
+}
+
+unsafe fn resource_name(program: GLuint, interface: GLenum, index: GLuint) -> String {
+    let [name_length] = resource_properties(program, interface, index, [gl::NAME_LENGTH]);
+    let mut name = vec![0u8; name_length.max(1) as usize];
+    let mut length = 0;
+    gl::GetProgramResourceName(program, interface, index, name.len() as GLsizei, &mut length, name.as_mut_ptr() as *mut GLchar);
+    name.truncate(length as usize);
+
+    let name = String::from_utf8_lossy(&name).into_owned();
+    match name.strip_suffix("[0]") {
+        Some(array_name) => array_name.to_string(),
+        None => name,
+    }
+}
+
+unsafe fn resource_properties<const N: usize>(program: GLuint, interface: GLenum, index: GLuint, properties: [GLenum; N]) -> [GLint; N] {
+    let mut values = [0; N];
+    gl::GetProgramResourceiv(program, interface, index, N as GLsizei, properties.as_ptr(), N as GLsizei, std::ptr::null_mut(), values.as_mut_ptr());
+    values
+}
+
+/// GLSL spelling of a type from reflection, for error messages
+pub fn type_name(gl_type: GLenum) -> &'static str {
+    match gl_type {
+        gl::FLOAT => "float",
+        gl::FLOAT_VEC2 => "vec2",
+        gl::FLOAT_VEC3 => "vec3",
+        gl::FLOAT_VEC4 => "vec4",
+        gl::INT => "int",
+        gl::INT_VEC2 => "ivec2",
+        gl::INT_VEC3 => "ivec3",
+        gl::INT_VEC4 => "ivec4",
+        gl::UNSIGNED_INT => "uint",
+        gl::UNSIGNED_INT_VEC2 => "uvec2",
+        gl::UNSIGNED_INT_VEC3 => "uvec3",
+        gl::UNSIGNED_INT_VEC4 => "uvec4",
+        gl::BOOL => "bool",
+        gl::FLOAT_MAT2 => "mat2",
+        gl::FLOAT_MAT3 => "mat3",
+        gl::FLOAT_MAT4 => "mat4",
+        gl::SAMPLER_2D => "sampler2D",
+        gl::SAMPLER_3D => "sampler3D",
+        gl::SAMPLER_CUBE => "samplerCube",
+        gl::SAMPLER_2D_SHADOW => "sampler2DShadow",
+        gl::SAMPLER_2D_ARRAY => "sampler2DArray",
+        gl::IMAGE_2D => "image2D",
+        _ => "unknown type",
+    }
+}
+
+/// a Rust value that can be written to a uniform of one of `GL_TYPES`
+pub trait UniformValue {
+    const GL_TYPES: &'static [GLenum];
+
+    unsafe fn set(&self, program: GLuint, location: GLint);
+}
+
+impl UniformValue for f32 {
+    const GL_TYPES: &'static [GLenum] = &[gl::FLOAT];
+
+    unsafe fn set(&self, program: GLuint, location: GLint) {
+        gl::ProgramUniform1f(program, location, *self);
+    }
+}
+
+/// also sets bools and the texture unit of samplers and images
+impl UniformValue for i32 {
+    const GL_TYPES: &'static [GLenum] = &[
+        gl::INT,
+        gl::BOOL,
+        gl::SAMPLER_2D,
+        gl::SAMPLER_3D,
+        gl::SAMPLER_CUBE,
+        gl::SAMPLER_2D_SHADOW,
+        gl::SAMPLER_2D_ARRAY,
+        gl::IMAGE_2D,
+    ];
+
+    unsafe fn set(&self, program: GLuint, location: GLint) {
+        gl::ProgramUniform1i(program, location, *self);
+    }
+}
+
+impl UniformValue for u32 {
+    const GL_TYPES: &'static [GLenum] = &[gl::UNSIGNED_INT, gl::BOOL];
+
+    unsafe fn set(&self, program: GLuint, location: GLint) {
+        gl::ProgramUniform1ui(program, location, *self);
+    }
+}
+
+impl UniformValue for bool {
+    const GL_TYPES: &'static [GLenum] = &[gl::BOOL];
+
+    unsafe fn set(&self, program: GLuint, location: GLint) {
+        gl::ProgramUniform1i(program, location, *self as GLint);
+    }
+}
+
+impl UniformValue for glm::Vec2 {
+    const GL_TYPES: &'static [GLenum] = &[gl::FLOAT_VEC2];
+
+    unsafe fn set(&self, program: GLuint, location: GLint) {
+        gl::ProgramUniform2fv(program, location, 1, self.as_ptr());
+    }
+}
+
+impl UniformValue for glm::Vec3 {
+    const GL_TYPES: &'static [GLenum] = &[gl::FLOAT_VEC3];
+
+    unsafe fn set(&self, program: GLuint, location: GLint) {
+        gl::ProgramUniform3fv(program, location, 1, self.as_ptr());
+    }
+}
+
+impl UniformValue for glm::Vec4 {
+    const GL_TYPES: &'static [GLenum] = &[gl::FLOAT_VEC4];
+
+    unsafe fn set(&self, program: GLuint, location: GLint) {
+        gl::ProgramUniform4fv(program, location, 1, self.as_ptr());
+    }
+}
+
+impl UniformValue for glm::Mat3 {
+    const GL_TYPES: &'static [GLenum] = &[gl::FLOAT_MAT3];
+
+    unsafe fn set(&self, program: GLuint, location: GLint) {
+        gl::ProgramUniformMatrix3fv(program, location, 1, gl::FALSE, self.as_ptr());
+    }
+}
+
+impl UniformValue for glm::Mat4 {
+    const GL_TYPES: &'static [GLenum] = &[gl::FLOAT_MAT4];
+
+    unsafe fn set(&self, program: GLuint, location: GLint) {
+        gl::ProgramUniformMatrix4fv(program, location, 1, gl::FALSE, self.as_ptr());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::headless::gl_test_context;
+    use crate::app::renderer::compute_program::ComputeProgram;
+    use crate::app::renderer::program_cache::{ProgramCache, ProgramKey};
+    use crate::app::renderer::shader::{stage_fixture, ShaderType};
+    use std::time::Duration;
+
+    fn block(data_size: usize) -> Reflection {
+        let block = Block {
+            binding: 0,
+            data_size,
+            members: BTreeMap::new(),
+        };
+        Reflection {
+            uniform_blocks: BTreeMap::from([("perFrameData".to_string(), block)]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn uniform_block_size_allows_std140_rounding() {
+        assert!(block(80).check_uniform_block_size("perFrameData", 80).is_ok());
+        assert!(block(68).check_uniform_block_size("perFrameData", 80).is_ok());
+
+        let error = block(96).check_uniform_block_size("perFrameData", 80).unwrap_err();
+        assert_eq!(error.to_string(), "uniform block perFrameData is 96 bytes in the shader but 80 bytes in Rust");
+        assert!(block(80).check_uniform_block_size("other", 80).is_err());
+    }
+
+    #[test]
+    fn reflects_and_sets_uniforms_by_name() {
+        let _context = gl_test_context();
+        let mut programs = ProgramCache::new(Duration::ZERO, None);
+        let textured = ProgramKey::new("shaders/vertex.glsl", "shaders/fragment.glsl").with_feature("TEXTURED", true);
+        let program = programs.get(&textured).unwrap();
+        let reflection = &program.reflection;
+
+        let per_frame_data = &reflection.uniform_blocks["perFrameData"];
+        assert_eq!(per_frame_data.binding, 0);
+        assert_eq!(per_frame_data.members["perspective_transform"].offset, 0);
+        assert_eq!(reflection.attributes["in_position"].location, 0);
+        assert_eq!(reflection.attributes["in_uv"].location, 2);
+        // unused by the textured variant
+        assert!(!reflection.attributes.contains_key("in_normal"));
+
+        program.set_uniform("texture0", 0).unwrap();
+        let error = program.set_uniform("texture0", 1.0).unwrap_err();
+        assert!(error.to_string().contains("is a sampler2D, can't set it from f32"), "{}", error);
+        assert!(program.set_uniform("missing", 0).is_err());
+
+        let compute = ComputeProgram::new(&stage_fixture(ShaderType::COMPUTE, "compute")).unwrap();
+        let values = &compute.program.reflection.storage_blocks["values"];
+        assert_eq!(values.members["data"].array_size, 0);
+    }
+}
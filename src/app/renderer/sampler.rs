@@ -0,0 +1,158 @@
+use gl::types::*;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::gl;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Filter {
+    NEAREST = gl::NEAREST as isize,
+    LINEAR = gl::LINEAR as isize,
+}
+
+#[allow(unused, non_camel_case_types)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Wrap {
+    REPEAT = gl::REPEAT as isize,
+    MIRRORED_REPEAT = gl::MIRRORED_REPEAT as isize,
+    CLAMP_TO_EDGE = gl::CLAMP_TO_EDGE as isize,
+    /// samples outside the texture read `border_color`
+    CLAMP_TO_BORDER = gl::CLAMP_TO_BORDER as isize,
+    MIRROR_CLAMP_TO_EDGE = gl::MIRROR_CLAMP_TO_EDGE as isize,
+}
+
+/// how a texture is sampled, set on the texture itself or on a `Sampler` shared between textures
+#[derive(Clone, Debug, PartialEq)]
+pub struct SamplerDescriptor {
+    /// used when the texture is drawn smaller than it is
+    pub min_filter: Filter,
+    /// used when the texture is drawn larger than it is
+    pub mag_filter: Filter,
+    /// how to pick between and blend mip levels, `None` only samples the base level
+    pub mipmap_filter: Option<Filter>,
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    /// 1 turns anisotropic filtering off, larger values are clamped to what the driver supports
+    pub max_anisotropy: f32,
+    pub border_color: [f32; 4],
+}
+
+impl Default for SamplerDescriptor {
+    /// trilinear filtering of a repeating texture
+    fn default() -> Self {
+        SamplerDescriptor {
+            min_filter: Filter::LINEAR,
+            mag_filter: Filter::LINEAR,
+            mipmap_filter: Some(Filter::LINEAR),
+            wrap_s: Wrap::REPEAT,
+            wrap_t: Wrap::REPEAT,
+            max_anisotropy: 1.0,
+            border_color: [0.0; 4],
+        }
+    }
+}
+
+impl SamplerDescriptor {
+    /// the GL minification filter, which combines `min_filter` and `mipmap_filter`
+    pub fn gl_min_filter(&self) -> GLenum {
+        match (self.min_filter, self.mipmap_filter) {
+            (Filter::NEAREST, None) => gl::NEAREST,
+            (Filter::LINEAR, None) => gl::LINEAR,
+            (Filter::NEAREST, Some(Filter::NEAREST)) => gl::NEAREST_MIPMAP_NEAREST,
+            (Filter::LINEAR, Some(Filter::NEAREST)) => gl::LINEAR_MIPMAP_NEAREST,
+            (Filter::NEAREST, Some(Filter::LINEAR)) => gl::NEAREST_MIPMAP_LINEAR,
+            (Filter::LINEAR, Some(Filter::LINEAR)) => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+
+    pub fn gl_mag_filter(&self) -> GLenum {
+        self.mag_filter as GLenum
+    }
+
+    /// sets every parameter on `object` through either the glTextureParameter or the glSamplerParameter functions
+    pub(super) unsafe fn apply(
+        &self,
+        object: GLuint,
+        parameter_i: unsafe fn(GLuint, GLenum, GLint),
+        parameter_f: unsafe fn(GLuint, GLenum, GLfloat),
+        parameter_fv: unsafe fn(GLuint, GLenum, *const GLfloat),
+    ) {
+        parameter_i(object, gl::TEXTURE_MIN_FILTER, self.gl_min_filter() as GLint);
+        parameter_i(object, gl::TEXTURE_MAG_FILTER, self.gl_mag_filter() as GLint);
+        parameter_i(object, gl::TEXTURE_WRAP_S, self.wrap_s as GLint);
+        parameter_i(object, gl::TEXTURE_WRAP_T, self.wrap_t as GLint);
+        parameter_fv(object, gl::TEXTURE_BORDER_COLOR, self.border_color.as_ptr());
+
+        if self.max_anisotropy > 1.0 {
+            let mut supported = 0.0;
+            gl::GetFloatv(gl::MAX_TEXTURE_MAX_ANISOTROPY, &mut supported);
+            if supported < self.max_anisotropy {
+                warn!("anisotropy of {} requested but the driver supports up to {}", self.max_anisotropy, supported);
+            }
+            parameter_f(object, gl::TEXTURE_MAX_ANISOTROPY, self.max_anisotropy.min(supported.max(1.0)));
+        }
+    }
+}
+
+/// sampling state kept apart from any texture. bound to a texture unit it overrides the parameters of
+/// whichever texture is bound there, so one sampler can be shared by many textures
+pub struct Sampler {
+    pub handle: u32,
+    #[allow(unused)]
+    pub descriptor: SamplerDescriptor,
+}
+
+impl Sampler {
+    pub fn new(descriptor: SamplerDescriptor) -> Self {
+        let sampler_id = unsafe {
+            let mut sampler: GLuint = 0;
+            gl::CreateSamplers(1, &mut sampler);
+            descriptor.apply(sampler, gl::SamplerParameteri, gl::SamplerParameterf, gl::SamplerParameterfv);
+            sampler
+        };
+        info!("created sampler #{} ({:?})", sampler_id, descriptor);
+
+        Sampler { handle: sampler_id, descriptor }
+    }
+
+    /// binds the sampler on its own, `Texture::bind` binds a texture's sampler with it
+    #[allow(unused)]
+    pub unsafe fn bind(&self, unit: u32) {
+        gl::BindSampler(unit, self.handle);
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        info!("deleting {}", self);
+        unsafe {
+            gl::DeleteSamplers(1, &self.handle);
+        }
+    }
+}
+
+impl fmt::Display for Sampler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sampler #{}", self.handle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn min_filter_combines_mipmap_filter() {
+        let descriptor = |min_filter, mipmap_filter| SamplerDescriptor {
+            min_filter,
+            mipmap_filter,
+            ..Default::default()
+        };
+
+        assert_eq!(SamplerDescriptor::default().gl_min_filter(), gl::LINEAR_MIPMAP_LINEAR);
+        assert_eq!(descriptor(Filter::NEAREST, None).gl_min_filter(), gl::NEAREST);
+        assert_eq!(descriptor(Filter::LINEAR, Some(Filter::NEAREST)).gl_min_filter(), gl::LINEAR_MIPMAP_NEAREST);
+        assert_eq!(descriptor(Filter::NEAREST, Some(Filter::LINEAR)).gl_min_filter(), gl::NEAREST_MIPMAP_LINEAR);
+    }
+}
@@ -0,0 +1,288 @@
+use anyhow::{anyhow, Result};
+
+/// index of a node in its `Scene`, only meaningful for the scene that returned it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+/// translation, rotation and scale relative to the parent, applied scale first
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: glm::Vec3,
+    pub rotation: glm::Quat,
+    pub scale: glm::Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform {
+            translation: glm::Vec3::zeros(),
+            rotation: glm::Quat::identity(),
+            scale: glm::vec3(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Transform {
+    #[cfg(test)]
+    pub fn from_translation(translation: glm::Vec3) -> Self {
+        Transform {
+            translation,
+            ..Default::default()
+        }
+    }
+
+    pub fn matrix(&self) -> glm::Mat4 {
+        glm::translation(&self.translation) * glm::quat_to_mat4(&self.rotation) * glm::scaling(&self.scale)
+    }
+}
+
+pub struct Node {
+    pub name: String,
+    /// index into the renderer's meshes, `None` for nodes that only group their children
+    pub mesh: Option<usize>,
+    /// index into the renderer's materials, the default material when `None`
+    pub material: Option<usize>,
+    /// index into the scene description's models, the model's nodes are this node's children
+    pub model: Option<usize>,
+    transform: Transform,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    world_matrix: glm::Mat4,
+    // set on a node and everything below it when its transform changes, so a clean node always has clean ancestors
+    dirty: bool,
+}
+
+impl Node {
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    #[allow(unused)]
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    /// local to world transform as of the last `Scene::update_world_matrices`
+    pub fn world_matrix(&self) -> &glm::Mat4 {
+        debug_assert!(!self.dirty, "world matrix of {} read before update_world_matrices", self.name);
+        &self.world_matrix
+    }
+}
+
+/// a hierarchy of nodes stored in one array, nodes are never removed so `NodeId`s stay valid
+#[derive(Default)]
+pub struct Scene {
+    nodes: Vec<Node>,
+    roots: Vec<NodeId>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn add_node(&mut self, name: &str, transform: Transform, parent: Option<NodeId>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Node {
+            name: name.to_string(),
+            mesh: None,
+            material: None,
+            model: None,
+            transform,
+            parent,
+            children: Vec::new(),
+            world_matrix: glm::Mat4::identity(),
+            dirty: true,
+        });
+
+        match parent {
+            Some(parent) => self.nodes[parent.0].children.push(id),
+            None => self.roots.push(id),
+        }
+        id
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    pub fn set_mesh(&mut self, id: NodeId, mesh: Option<usize>) {
+        self.nodes[id.0].mesh = mesh;
+    }
+
+    pub fn set_material(&mut self, id: NodeId, material: Option<usize>) {
+        self.nodes[id.0].material = material;
+    }
+
+    pub fn set_model(&mut self, id: NodeId, model: Option<usize>) {
+        self.nodes[id.0].model = model;
+    }
+
+    #[allow(unused)]
+    pub fn set_transform(&mut self, id: NodeId, transform: Transform) {
+        self.nodes[id.0].transform = transform;
+        self.mark_dirty(id);
+    }
+
+    /// moves `id` under `parent`, or to the top level for `None`. the local transform is kept,
+    /// so the node moves along with its new parent
+    #[allow(unused)]
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> Result<()> {
+        let mut ancestor = parent;
+        while let Some(ancestor_id) = ancestor {
+            if ancestor_id == id {
+                return Err(anyhow!("can't parent {} to its own descendant", self.node(id).name));
+            }
+            ancestor = self.node(ancestor_id).parent;
+        }
+
+        match self.nodes[id.0].parent {
+            Some(old_parent) => self.nodes[old_parent.0].children.retain(|&child| child != id),
+            None => self.roots.retain(|&root| root != id),
+        }
+        match parent {
+            Some(parent) => self.nodes[parent.0].children.push(id),
+            None => self.roots.push(id),
+        }
+        self.nodes[id.0].parent = parent;
+        self.mark_dirty(id);
+        Ok(())
+    }
+
+    fn mark_dirty(&mut self, id: NodeId) {
+        self.nodes[id.0].dirty = true;
+        let mut stack = self.nodes[id.0].children.clone();
+        while let Some(id) = stack.pop() {
+            let node = &mut self.nodes[id.0];
+            // the rest of its subtree is dirty already
+            if node.dirty {
+                continue;
+            }
+            node.dirty = true;
+            stack.extend_from_slice(&node.children);
+        }
+    }
+
+    /// recomputes the world matrix of every node changed since the last update, returns how many were recomputed
+    pub fn update_world_matrices(&mut self) -> usize {
+        let mut updated = 0;
+        let mut stack: Vec<(NodeId, glm::Mat4)> = self.roots.iter().rev().map(|&root| (root, glm::Mat4::identity())).collect();
+
+        while let Some((id, parent_world_matrix)) = stack.pop() {
+            let node = &mut self.nodes[id.0];
+            if node.dirty {
+                node.world_matrix = parent_world_matrix * node.transform.matrix();
+                node.dirty = false;
+                updated += 1;
+            }
+            let world_matrix = node.world_matrix;
+            stack.extend(node.children.iter().rev().map(|&child| (child, world_matrix)));
+        }
+
+        updated
+    }
+
+    /// every node depth first, parents before their children
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &Node)> + '_ {
+        let mut stack: Vec<NodeId> = self.roots.iter().rev().copied().collect();
+        std::iter::from_fn(move || {
+            let id = stack.pop()?;
+            let node = &self.nodes[id.0];
+            stack.extend(node.children.iter().rev());
+            Some((id, node))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn world_position(scene: &Scene, id: NodeId) -> glm::Vec3 {
+        (scene.node(id).world_matrix() * glm::vec4(0.0, 0.0, 0.0, 1.0)).xyz()
+    }
+
+    fn assert_near(a: &glm::Vec3, b: &glm::Vec3) {
+        assert!(glm::distance(a, b) < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn world_matrices_include_every_ancestor() {
+        let mut scene = Scene::new();
+        let parent = scene.add_node(
+            "parent",
+            Transform {
+                translation: glm::vec3(10.0, 0.0, 0.0),
+                rotation: glm::quat_angle_axis(FRAC_PI_2, &glm::Vec3::y()),
+                scale: glm::vec3(2.0, 2.0, 2.0),
+            },
+            None,
+        );
+        let child = scene.add_node("child", Transform::from_translation(glm::vec3(1.0, 0.0, 0.0)), Some(parent));
+        let grandchild = scene.add_node("grandchild", Transform::from_translation(glm::vec3(0.0, 1.0, 0.0)), Some(child));
+
+        assert_eq!(scene.update_world_matrices(), 3);
+
+        // +x scaled by 2 then turned a quarter about y ends up along -z
+        assert_near(&world_position(&scene, parent), &glm::vec3(10.0, 0.0, 0.0));
+        assert_near(&world_position(&scene, child), &glm::vec3(10.0, 0.0, -2.0));
+        assert_near(&world_position(&scene, grandchild), &glm::vec3(10.0, 2.0, -2.0));
+    }
+
+    #[test]
+    fn only_changed_subtrees_are_recomputed() {
+        let mut scene = Scene::new();
+        let a = scene.add_node("a", Transform::default(), None);
+        let a_child = scene.add_node("a child", Transform::from_translation(glm::vec3(0.0, 0.0, 1.0)), Some(a));
+        let b = scene.add_node("b", Transform::default(), None);
+        scene.add_node("b child", Transform::default(), Some(b));
+        scene.update_world_matrices();
+
+        assert_eq!(scene.update_world_matrices(), 0);
+
+        scene.set_transform(a, Transform::from_translation(glm::vec3(5.0, 0.0, 0.0)));
+        assert_eq!(scene.update_world_matrices(), 2);
+        assert_near(&world_position(&scene, a_child), &glm::vec3(5.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn reparenting_keeps_local_transform() {
+        let mut scene = Scene::new();
+        let a = scene.add_node("a", Transform::from_translation(glm::vec3(1.0, 0.0, 0.0)), None);
+        let b = scene.add_node("b", Transform::from_translation(glm::vec3(0.0, 1.0, 0.0)), None);
+        scene.update_world_matrices();
+
+        scene.set_parent(b, Some(a)).unwrap();
+        scene.update_world_matrices();
+
+        assert_near(&world_position(&scene, b), &glm::vec3(1.0, 1.0, 0.0));
+        assert_eq!(scene.node(a).children(), &[b]);
+        assert_eq!(scene.node(b).parent(), Some(a));
+
+        // a can't go under its own child
+        assert!(scene.set_parent(a, Some(b)).is_err());
+        assert!(scene.set_parent(a, Some(a)).is_err());
+    }
+
+    #[test]
+    fn iterates_depth_first() {
+        let mut scene = Scene::new();
+        let a = scene.add_node("a", Transform::default(), None);
+        let b = scene.add_node("b", Transform::default(), None);
+        scene.add_node("a1", Transform::default(), Some(a));
+        scene.add_node("b1", Transform::default(), Some(b));
+        scene.add_node("a2", Transform::default(), Some(a));
+
+        let names: Vec<&str> = scene.iter().map(|(_, node)| node.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "a1", "a2", "b", "b1"]);
+    }
+}
@@ -0,0 +1,718 @@
+//! scene description files, written in RON so they can be edited by hand and versioned in git.
+//!
+//! meshes, samplers, textures and materials are declared once by name and referred to by that name from
+//! nodes, materials and textures. glTF and GLB files are declared as models, a node that names one gets the
+//! model's nodes as its children, drawn with the model's own meshes, materials and textures. paths are relative to the working directory, angles are in degrees and
+//! rotations are `(x, y, z, w)` quaternions. every field except names and paths has a default,
+//! so a file only needs to mention what it changes.
+//!
+//! ```ron
+//! (
+//!     meshes: [(name: "cube", path: "meshes/cube.obj")],
+//!     samplers: [(name: "clamped", wrap_s: CLAMP_TO_EDGE, wrap_t: CLAMP_TO_EDGE, max_anisotropy: 8.0)],
+//!     textures: [(name: "stone", path: "textures/stone.png", sampler: Some("clamped"))],
+//!     materials: [(name: "stone", base_color_texture: Some("stone"))],
+//!     models: [(name: "helmet", path: "models/helmet.glb")],
+//!     nodes: [(name: "cube", mesh: Some("cube"), material: Some("stone")), (name: "helmet", model: Some("helmet"))],
+//!     camera: Orbit(distance: 3.5),
+//! )
+//! ```
+
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::rc::Rc;
+
+use super::gltf_import::ImportedScene;
+
+use super::camera::{Camera, FlyCamera, OrbitCamera};
+use super::intrinsics::{Degrees, FieldOfView, Radians};
+use super::material::Material;
+use super::mesh::Mesh;
+use super::projection::Projection;
+use super::scene::{NodeId, Scene, Transform};
+use super::sampler::{Filter, Sampler, SamplerDescriptor, Wrap};
+use super::texture::{Texture, TextureDescriptor};
+use super::Renderer;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneDescription {
+    pub meshes: Vec<AssetDescription>,
+    pub samplers: Vec<SamplerDescription>,
+    pub textures: Vec<TextureDescription>,
+    pub materials: Vec<MaterialDescription>,
+    /// glTF or GLB files, loaded once however many nodes instance them
+    pub models: Vec<AssetDescription>,
+    /// top level nodes, each with its children nested inside it
+    pub nodes: Vec<NodeDescription>,
+    pub lights: Vec<Light>,
+    pub camera: CameraDescription,
+    pub projection: ProjectionDescription,
+}
+
+/// a file loaded once and shared by name
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AssetDescription {
+    pub name: String,
+    pub path: String,
+}
+
+/// sampling state shared by every texture that names it, textures without one use the default sampling
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SamplerDescription {
+    pub name: String,
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    pub mipmap_filter: Option<Filter>,
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    pub max_anisotropy: f32,
+    pub border_color: [f32; 4],
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextureDescription {
+    pub name: String,
+    pub path: String,
+    pub mipmaps: bool,
+    pub sampler: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaterialDescription {
+    pub name: String,
+    pub base_color_factor: [f32; 4],
+    pub base_color_texture: Option<String>,
+    pub normal_texture: Option<String>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub metallic_roughness_texture: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NodeDescription {
+    pub name: String,
+    pub translation: [f32; 3],
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+    pub mesh: Option<String>,
+    pub material: Option<String>,
+    /// a node that instances a model gets its children from the model, so it can't have any of its own
+    pub model: Option<String>,
+    pub children: Vec<NodeDescription>,
+}
+
+/// lights are kept with the scene and saved back out, the shaders don't light anything yet
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Light {
+    Directional { direction: [f32; 3], color: [f32; 3], intensity: f32 },
+    Point { position: [f32; 3], color: [f32; 3], intensity: f32 },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum CameraDescription {
+    Fly {
+        #[serde(default)]
+        position: [f32; 3],
+        #[serde(default)]
+        yaw: f32,
+        #[serde(default)]
+        pitch: f32,
+    },
+    Orbit {
+        #[serde(default)]
+        target: [f32; 3],
+        distance: f32,
+        #[serde(default)]
+        yaw: f32,
+        #[serde(default)]
+        pitch: f32,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FieldOfViewDescription {
+    Vertical(f32),
+    Horizontal(f32),
+    FocalLength { focal_length: f32, sensor_height: f32 },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ProjectionDescription {
+    Perspective {
+        field_of_view: FieldOfViewDescription,
+        near_clipping_plane: f32,
+        far_clipping_plane: f32,
+    },
+    Orthographic {
+        height: f32,
+        near_clipping_plane: f32,
+        far_clipping_plane: f32,
+    },
+    ReversedInfinitePerspective {
+        field_of_view: FieldOfViewDescription,
+        near_clipping_plane: f32,
+    },
+}
+
+impl Default for MaterialDescription {
+    fn default() -> Self {
+        let material = Material::default();
+        MaterialDescription {
+            name: String::new(),
+            base_color_factor: material.base_color_factor,
+            base_color_texture: None,
+            normal_texture: None,
+            metallic_factor: material.metallic_factor,
+            roughness_factor: material.roughness_factor,
+            metallic_roughness_texture: None,
+        }
+    }
+}
+
+impl Default for SamplerDescription {
+    fn default() -> Self {
+        let sampler = SamplerDescriptor::default();
+        SamplerDescription {
+            name: String::new(),
+            min_filter: sampler.min_filter,
+            mag_filter: sampler.mag_filter,
+            mipmap_filter: sampler.mipmap_filter,
+            wrap_s: sampler.wrap_s,
+            wrap_t: sampler.wrap_t,
+            max_anisotropy: sampler.max_anisotropy,
+            border_color: sampler.border_color,
+        }
+    }
+}
+
+impl From<&SamplerDescription> for SamplerDescriptor {
+    fn from(description: &SamplerDescription) -> Self {
+        SamplerDescriptor {
+            min_filter: description.min_filter,
+            mag_filter: description.mag_filter,
+            mipmap_filter: description.mipmap_filter,
+            wrap_s: description.wrap_s,
+            wrap_t: description.wrap_t,
+            max_anisotropy: description.max_anisotropy,
+            border_color: description.border_color,
+        }
+    }
+}
+
+impl Default for TextureDescription {
+    fn default() -> Self {
+        TextureDescription {
+            name: String::new(),
+            path: String::new(),
+            mipmaps: TextureDescriptor::default().mipmaps,
+            sampler: None,
+        }
+    }
+}
+
+impl Default for NodeDescription {
+    fn default() -> Self {
+        NodeDescription {
+            name: String::new(),
+            translation: [0.0; 3],
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            scale: [1.0; 3],
+            mesh: None,
+            material: None,
+            model: None,
+            children: Vec::new(),
+        }
+    }
+}
+
+impl Default for CameraDescription {
+    fn default() -> Self {
+        CameraDescription::from(&super::default_camera())
+    }
+}
+
+impl Default for ProjectionDescription {
+    fn default() -> Self {
+        ProjectionDescription::from(&super::default_projection())
+    }
+}
+
+impl SceneDescription {
+    pub fn load(source_file: &str) -> Result<Self> {
+        let source = fs::read_to_string(source_file).with_context(|| format!("failed to read scene file {}", source_file))?;
+        SceneDescription::parse(&source).with_context(|| format!("failed to parse scene file {}", source_file))
+    }
+
+    pub fn parse(source: &str) -> Result<Self> {
+        Ok(ron::from_str(source)?)
+    }
+
+    pub fn save(&self, output_file: &str) -> Result<()> {
+        fs::write(output_file, self.to_ron()?).with_context(|| format!("failed to write scene file {}", output_file))?;
+        info!("saved scene to {}", output_file);
+        Ok(())
+    }
+
+    pub fn to_ron(&self) -> Result<String> {
+        let config = PrettyConfig::new().struct_names(false);
+        Ok(ron::ser::to_string_pretty(self, config)? + "\n")
+    }
+}
+
+fn name_lookup<'a>(names: impl Iterator<Item = &'a str>, kind: &str) -> Result<HashMap<&'a str, usize>> {
+    let mut lookup = HashMap::new();
+    for (index, name) in names.enumerate() {
+        if lookup.insert(name, index).is_some() {
+            return Err(anyhow!("{} \"{}\" is declared more than once", kind, name));
+        }
+    }
+    Ok(lookup)
+}
+
+fn resolve(lookup: &HashMap<&str, usize>, name: &Option<String>, kind: &str) -> Result<Option<usize>> {
+    match name {
+        Some(name) => lookup.get(name.as_str()).copied().map(Some).ok_or_else(|| anyhow!("unknown {} \"{}\"", kind, name)),
+        None => Ok(None),
+    }
+}
+
+impl SceneDescription {
+    /// builds the node hierarchy, mesh, material and model names become indices into `meshes`, `materials` and `models`.
+    /// models aren't instanced yet, that needs them loaded
+    pub fn build_scene(&self) -> Result<Scene> {
+        let meshes = name_lookup(self.meshes.iter().map(|mesh| mesh.name.as_str()), "mesh")?;
+        let materials = name_lookup(self.materials.iter().map(|material| material.name.as_str()), "material")?;
+        let models = name_lookup(self.models.iter().map(|model| model.name.as_str()), "model")?;
+
+        let mut scene = Scene::new();
+        let mut stack: Vec<(&NodeDescription, Option<NodeId>)> = self.nodes.iter().rev().map(|node| (node, None)).collect();
+        while let Some((description, parent)) = stack.pop() {
+            let id = scene.add_node(&description.name, description.transform()?, parent);
+            let mesh = resolve(&meshes, &description.mesh, "mesh").with_context(|| format!("in node \"{}\"", description.name))?;
+            let material = resolve(&materials, &description.material, "material").with_context(|| format!("in node \"{}\"", description.name))?;
+            let model = resolve(&models, &description.model, "model").with_context(|| format!("in node \"{}\"", description.name))?;
+            if model.is_some() && !description.children.is_empty() {
+                return Err(anyhow!("node \"{}\" instances a model and can't have children of its own", description.name));
+            }
+            scene.set_mesh(id, mesh);
+            scene.set_material(id, material);
+            scene.set_model(id, model);
+            stack.extend(description.children.iter().rev().map(|child| (child, Some(id))));
+        }
+
+        Ok(scene)
+    }
+
+    pub fn build_materials(&self) -> Result<Vec<Material>> {
+        let textures = name_lookup(self.textures.iter().map(|texture| texture.name.as_str()), "texture")?;
+
+        self.materials
+            .iter()
+            .map(|description| {
+                let texture = |name| resolve(&textures, name, "texture").with_context(|| format!("in material \"{}\"", description.name));
+                Ok(Material {
+                    name: Some(description.name.clone()),
+                    base_color_factor: description.base_color_factor,
+                    base_color_texture: texture(&description.base_color_texture)?,
+                    normal_texture: texture(&description.normal_texture)?,
+                    metallic_factor: description.metallic_factor,
+                    roughness_factor: description.roughness_factor,
+                    metallic_roughness_texture: texture(&description.metallic_roughness_texture)?,
+                })
+            })
+            .collect()
+    }
+
+    /// the index into `samplers` of each texture's sampler
+    pub fn build_texture_samplers(&self) -> Result<Vec<Option<usize>>> {
+        let samplers = name_lookup(self.samplers.iter().map(|sampler| sampler.name.as_str()), "sampler")?;
+
+        self.textures
+            .iter()
+            .map(|texture| resolve(&samplers, &texture.sampler, "sampler").with_context(|| format!("in texture \"{}\"", texture.name)))
+            .collect()
+    }
+
+    /// the inverse of `build_scene`, replaces `nodes` with the hierarchy in `scene`. the nodes instanced from models are left out
+    pub fn describe_scene(&mut self, scene: &Scene) {
+        let mesh_names: Vec<String> = self.meshes.iter().map(|mesh| mesh.name.clone()).collect();
+        let material_names: Vec<String> = self.materials.iter().map(|material| material.name.clone()).collect();
+        let model_names: Vec<String> = self.models.iter().map(|model| model.name.clone()).collect();
+
+        let describe = |id: NodeId| {
+            let node = scene.node(id);
+            let transform = node.transform();
+            NodeDescription {
+                name: node.name.clone(),
+                translation: transform.translation.into(),
+                rotation: transform.rotation.coords.into(),
+                scale: transform.scale.into(),
+                mesh: node.mesh.map(|index| mesh_names[index].clone()),
+                material: node.material.map(|index| material_names[index].clone()),
+                model: node.model.map(|index| model_names[index].clone()),
+                children: Vec::new(),
+            }
+        };
+
+        fn describe_tree(scene: &Scene, id: NodeId, describe: &dyn Fn(NodeId) -> NodeDescription) -> NodeDescription {
+            let mut description = describe(id);
+            if description.model.is_none() {
+                description.children = scene.node(id).children().iter().map(|&child| describe_tree(scene, child, describe)).collect();
+            }
+            description
+        }
+
+        self.nodes = scene.roots().iter().map(|&root| describe_tree(scene, root, &describe)).collect();
+    }
+}
+
+impl NodeDescription {
+    fn transform(&self) -> Result<Transform> {
+        let [x, y, z, w] = self.rotation;
+        let rotation = glm::quat(x, y, z, w);
+        // normalizing would divide by zero and fill the world matrices with NaN
+        if rotation.norm() == 0.0 {
+            return Err(anyhow!("node \"{}\" has a zero length rotation", self.name));
+        }
+        Ok(Transform {
+            translation: self.translation.into(),
+            rotation: glm::quat_normalize(&rotation),
+            scale: self.scale.into(),
+        })
+    }
+}
+
+impl From<&CameraDescription> for Camera {
+    fn from(description: &CameraDescription) -> Self {
+        let radians = |degrees: f32| Radians::from(Degrees(degrees)).0;
+        match *description {
+            CameraDescription::Fly { position, yaw, pitch } => Camera::Fly(FlyCamera {
+                yaw: radians(yaw),
+                pitch: radians(pitch),
+                ..FlyCamera::new(position.into())
+            }),
+            CameraDescription::Orbit { target, distance, yaw, pitch } => {
+                let mut orbit = OrbitCamera::new(target.into(), distance);
+                orbit.align(radians(yaw), radians(pitch));
+                Camera::Orbit(orbit)
+            }
+        }
+    }
+}
+
+impl From<&Camera> for CameraDescription {
+    fn from(camera: &Camera) -> Self {
+        let degrees = |radians: f32| Degrees::from(Radians(radians)).0;
+        match camera {
+            Camera::Fly(fly) => CameraDescription::Fly {
+                position: fly.position.into(),
+                yaw: degrees(fly.yaw),
+                pitch: degrees(fly.pitch),
+            },
+            Camera::Orbit(orbit) => CameraDescription::Orbit {
+                target: orbit.target.into(),
+                distance: orbit.distance,
+                yaw: degrees(orbit.yaw),
+                pitch: degrees(orbit.pitch),
+            },
+        }
+    }
+}
+
+impl From<&FieldOfViewDescription> for FieldOfView {
+    fn from(description: &FieldOfViewDescription) -> Self {
+        match *description {
+            FieldOfViewDescription::Vertical(degrees) => FieldOfView::Vertical(Degrees(degrees).into()),
+            FieldOfViewDescription::Horizontal(degrees) => FieldOfView::Horizontal(Degrees(degrees).into()),
+            FieldOfViewDescription::FocalLength { focal_length, sensor_height } => FieldOfView::FocalLength { focal_length, sensor_height },
+        }
+    }
+}
+
+impl From<&FieldOfView> for FieldOfViewDescription {
+    fn from(field_of_view: &FieldOfView) -> Self {
+        match *field_of_view {
+            FieldOfView::Vertical(radians) => FieldOfViewDescription::Vertical(Degrees::from(radians).0),
+            FieldOfView::Horizontal(radians) => FieldOfViewDescription::Horizontal(Degrees::from(radians).0),
+            FieldOfView::FocalLength { focal_length, sensor_height } => FieldOfViewDescription::FocalLength { focal_length, sensor_height },
+        }
+    }
+}
+
+impl From<&ProjectionDescription> for Projection {
+    fn from(description: &ProjectionDescription) -> Self {
+        match description {
+            ProjectionDescription::Perspective {
+                field_of_view,
+                near_clipping_plane,
+                far_clipping_plane,
+            } => Projection::Perspective {
+                field_of_view: field_of_view.into(),
+                near_clipping_plane: *near_clipping_plane,
+                far_clipping_plane: *far_clipping_plane,
+            },
+            ProjectionDescription::Orthographic {
+                height,
+                near_clipping_plane,
+                far_clipping_plane,
+            } => Projection::Orthographic {
+                height: *height,
+                near_clipping_plane: *near_clipping_plane,
+                far_clipping_plane: *far_clipping_plane,
+            },
+            ProjectionDescription::ReversedInfinitePerspective {
+                field_of_view,
+                near_clipping_plane,
+            } => Projection::ReversedInfinitePerspective {
+                field_of_view: field_of_view.into(),
+                near_clipping_plane: *near_clipping_plane,
+            },
+        }
+    }
+}
+
+impl From<&Projection> for ProjectionDescription {
+    fn from(projection: &Projection) -> Self {
+        match projection {
+            Projection::Perspective {
+                field_of_view,
+                near_clipping_plane,
+                far_clipping_plane,
+            } => ProjectionDescription::Perspective {
+                field_of_view: field_of_view.into(),
+                near_clipping_plane: *near_clipping_plane,
+                far_clipping_plane: *far_clipping_plane,
+            },
+            Projection::Orthographic {
+                height,
+                near_clipping_plane,
+                far_clipping_plane,
+            } => ProjectionDescription::Orthographic {
+                height: *height,
+                near_clipping_plane: *near_clipping_plane,
+                far_clipping_plane: *far_clipping_plane,
+            },
+            Projection::ReversedInfinitePerspective {
+                field_of_view,
+                near_clipping_plane,
+            } => ProjectionDescription::ReversedInfinitePerspective {
+                field_of_view: field_of_view.into(),
+                near_clipping_plane: *near_clipping_plane,
+            },
+        }
+    }
+}
+
+impl Renderer {
+    /// replaces everything drawn with the contents of `description`, loading its meshes and textures.
+    /// nothing changes if any part of it fails to load
+    pub fn load_scene(&mut self, description: &SceneDescription) -> Result<()> {
+        let mut scene = description.build_scene()?;
+        let mut materials = description.build_materials()?;
+        let texture_samplers = description.build_texture_samplers()?;
+        let mut meshes = description.meshes.iter().map(|mesh| Mesh::new(&mesh.path).with_context(|| format!("in mesh \"{}\"", mesh.name))).collect::<Result<Vec<_>>>()?;
+        let samplers: Vec<Rc<Sampler>> = description.samplers.iter().map(|sampler| Rc::new(Sampler::new(sampler.into()))).collect();
+        let mut textures = description
+            .textures
+            .iter()
+            .zip(texture_samplers)
+            .map(|(texture, sampler)| {
+                let descriptor = TextureDescriptor {
+                    mipmaps: texture.mipmaps,
+                    ..Default::default()
+                };
+                let loaded = Texture::new(&texture.path, &descriptor).with_context(|| format!("in texture \"{}\"", texture.name))?;
+                Ok(match sampler {
+                    Some(index) => loaded.with_sampler(samplers[index].clone()),
+                    None => loaded,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // every model's meshes, materials and textures go after the described ones, once per model
+        let mut model_offsets = Vec::new();
+        for model in &description.models {
+            let imported = ImportedScene::load(&model.path).with_context(|| format!("in model \"{}\"", model.name))?;
+            let (first_mesh, first_material, first_texture) = (meshes.len(), materials.len(), textures.len());
+            meshes.extend(imported.create_meshes().with_context(|| format!("in model \"{}\"", model.name))?);
+            textures.extend(imported.create_textures(&TextureDescriptor::default()).with_context(|| format!("in model \"{}\"", model.name))?);
+            materials.extend(imported.scene_materials(first_texture));
+            model_offsets.push((imported, first_mesh, first_material));
+        }
+        let instances: Vec<(NodeId, usize)> = scene.iter().filter_map(|(id, node)| Some((id, node.model?))).collect();
+        for (id, model) in instances {
+            let (imported, first_mesh, first_material) = &model_offsets[model];
+            imported.instantiate(&mut scene, id, *first_mesh, *first_material);
+        }
+
+        self.scene = scene;
+        self.materials = materials;
+        self.meshes = meshes;
+        self.textures = textures;
+        self.lights = description.lights.clone();
+        self.camera = (&description.camera).into();
+        self.draw_config.projection = (&description.projection).into();
+        self.scene_description = description.clone();
+
+        info!("loaded scene with {} meshes, {} textures and {} materials", self.meshes.len(), self.textures.len(), self.materials.len());
+        Ok(())
+    }
+
+    /// the loaded scene with its current node transforms, camera and projection, ready to save
+    pub fn scene_description(&self) -> SceneDescription {
+        let mut description = self.scene_description.clone();
+        description.describe_scene(&self.scene);
+        description.lights = self.lights.clone();
+        description.camera = (&self.camera).into();
+        description.projection = (&self.draw_config.projection).into();
+        description
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUBE_SCENE: &str = include_str!("../../../scenes/cube.ron");
+
+    #[test]
+    fn parses_bundled_scene() {
+        let description = SceneDescription::parse(CUBE_SCENE).unwrap();
+
+        assert_eq!(description.meshes[0].path, "meshes/cube.obj");
+        let scene = description.build_scene().unwrap();
+        let (_, cube) = scene.iter().next().unwrap();
+        assert_eq!(cube.name, "cube");
+        assert_eq!(cube.mesh, Some(0));
+        assert_eq!(cube.material, Some(0));
+        assert_eq!(description.build_materials().unwrap()[0].base_color_texture, Some(0));
+    }
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let description = SceneDescription::parse("(nodes: [(name: \"empty\", children: [(name: \"child\")])])").unwrap();
+
+        assert_eq!(description.nodes[0].scale, [1.0; 3]);
+        assert_eq!(description.nodes[0].children[0].rotation, [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(description.camera, CameraDescription::default());
+        assert_eq!(description.projection, ProjectionDescription::default());
+    }
+
+    #[test]
+    fn textures_share_named_samplers() {
+        let description = SceneDescription::parse(
+            "(samplers: [(name: \"pixelated\", min_filter: NEAREST, mag_filter: NEAREST, mipmap_filter: None, wrap_s: CLAMP_TO_BORDER)],
+              textures: [(name: \"a\", path: \"a.png\", sampler: Some(\"pixelated\")), (name: \"b\", path: \"b.png\", mipmaps: false), (name: \"c\", path: \"c.png\", sampler: Some(\"pixelated\"))])",
+        )
+        .unwrap();
+
+        assert_eq!(description.build_texture_samplers().unwrap(), [Some(0), None, Some(0)]);
+        assert!(description.textures[0].mipmaps);
+        assert!(!description.textures[1].mipmaps);
+
+        let sampler = SamplerDescriptor::from(&description.samplers[0]);
+        assert_eq!((sampler.min_filter, sampler.mipmap_filter), (Filter::NEAREST, None));
+        assert_eq!(sampler.wrap_s, Wrap::CLAMP_TO_BORDER);
+        // unmentioned fields keep the defaults
+        assert_eq!(sampler.wrap_t, Wrap::REPEAT);
+        assert_eq!(sampler.max_anisotropy, 1.0);
+    }
+
+    #[test]
+    fn round_trips_through_scene_and_ron() {
+        let mut description = SceneDescription::parse(CUBE_SCENE).unwrap();
+        description.nodes[0].children.push(NodeDescription {
+            name: "moon".to_string(),
+            translation: [2.0, 0.5, 0.0],
+            rotation: [0.0, 1.0, 0.0, 0.0],
+            mesh: Some("cube".to_string()),
+            ..Default::default()
+        });
+        description.lights.push(Light::Point {
+            position: [1.0, 2.0, 3.0],
+            color: [1.0, 0.9, 0.8],
+            intensity: 10.0,
+        });
+        description.camera = CameraDescription::Fly {
+            position: [0.0, 1.0, 5.0],
+            yaw: 30.0,
+            pitch: -10.0,
+        };
+
+        let scene = description.build_scene().unwrap();
+        let mut rebuilt = description.clone();
+        rebuilt.nodes.clear();
+        rebuilt.describe_scene(&scene);
+        assert_eq!(rebuilt, description);
+
+        let reparsed = SceneDescription::parse(&description.to_ron().unwrap()).unwrap();
+        assert_eq!(reparsed, description);
+
+        // angles survive the trip through radians
+        let camera = CameraDescription::from(&Camera::from(&description.camera));
+        let CameraDescription::Fly { yaw, .. } = camera else { unreachable!() };
+        assert!((yaw - 30.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn rejects_unknown_and_duplicate_names() {
+        let unknown = SceneDescription::parse("(nodes: [(name: \"a\", mesh: Some(\"missing\"))])").unwrap();
+        let Err(error) = unknown.build_scene() else { panic!("missing mesh was accepted") };
+        assert!(format!("{:#}", error).contains("unknown mesh \"missing\""), "{:#}", error);
+
+        let duplicate = SceneDescription::parse("(materials: [(name: \"a\"), (name: \"a\")])").unwrap();
+        assert!(duplicate.build_scene().is_err());
+
+        let unknown = SceneDescription::parse("(textures: [(name: \"t\", path: \"t.png\", sampler: Some(\"missing\"))])").unwrap();
+        let Err(error) = unknown.build_texture_samplers() else { panic!("missing sampler was accepted") };
+        assert!(format!("{:#}", error).contains("unknown sampler \"missing\""), "{:#}", error);
+    }
+    #[test]
+    fn nodes_instance_models_without_saving_their_nodes() {
+        let description = SceneDescription::parse(
+            "(models: [(name: \"triangle\", path: \"tests/fixtures/gltf/triangle.gltf\")],
+              nodes: [(name: \"group\", children: [(name: \"instance\", model: Some(\"triangle\"))])])",
+        )
+        .unwrap();
+
+        let mut scene = description.build_scene().unwrap();
+        let (instance, node) = scene.iter().find(|(_, node)| node.name == "instance").unwrap();
+        assert_eq!(node.model, Some(0));
+
+        ImportedScene::load(&description.models[0].path).unwrap().instantiate(&mut scene, instance, 0, 0);
+        assert_eq!(scene.node(instance).children().len(), 2);
+
+        let mut rebuilt = description.clone();
+        rebuilt.describe_scene(&scene);
+        assert_eq!(rebuilt, description);
+    }
+
+    #[test]
+    fn rejects_zero_length_rotations() {
+        let description = SceneDescription::parse("(nodes: [(name: \"spun\", rotation: (0.0, 0.0, 0.0, 0.0))])").unwrap();
+
+        let Err(error) = description.build_scene() else { panic!("zero rotation was accepted") };
+        assert_eq!(error.to_string(), "node \"spun\" has a zero length rotation");
+    }
+
+    #[test]
+    fn rejects_models_on_nodes_with_children() {
+        let description = SceneDescription::parse(
+            "(models: [(name: \"triangle\", path: \"triangle.gltf\")],
+              nodes: [(name: \"instance\", model: Some(\"triangle\"), children: [(name: \"child\")])])",
+        )
+        .unwrap();
+
+        let Err(error) = description.build_scene() else { panic!("model with children was accepted") };
+        assert_eq!(error.to_string(), "node \"instance\" instances a model and can't have children of its own");
+    }
+}
@@ -1,30 +1,81 @@
 use anyhow::{anyhow, Context, Result};
 use gl::types::*;
 use log::info;
-use std::ffi::CString;
+use std::ffi::{c_void, CString};
 use std::fmt;
 use std::fs;
+use std::path::{Path, PathBuf};
 
 use super::gl;
+use super::preprocessor::{self, Preprocessed};
+use super::shader_error::CompileError;
+use super::supports;
 
 pub struct Shader {
     pub handle: u32,
     pub shader_type: ShaderType,
+    pub source_file: String,
+    pub defines: Vec<(String, String)>,
+    /// `source_file` and everything it includes
+    #[allow(unused)]
+    pub source_files: Vec<PathBuf>,
+    /// entry point and constants a SPIR-V shader was specialized with, `None` for GLSL
+    pub spirv: Option<Specialization>,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Specialization {
+    pub entry_point: String,
+    /// (constant_id, value) pairs, values are the raw 32 bits so floats are passed as `f32::to_bits`
+    pub constants: Vec<(u32, u32)>,
+}
+
+// first word of every SPIR-V module
+const SPIRV_MAGIC: u32 = 0x07230203;
+
+#[allow(non_camel_case_types)]
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ShaderType {
     FRAGMENT = gl::FRAGMENT_SHADER as isize,
     VERTEX = gl::VERTEX_SHADER as isize,
+    GEOMETRY = gl::GEOMETRY_SHADER as isize,
+    TESS_CONTROL = gl::TESS_CONTROL_SHADER as isize,
+    TESS_EVALUATION = gl::TESS_EVALUATION_SHADER as isize,
+    COMPUTE = gl::COMPUTE_SHADER as isize,
+}
+
+impl ShaderType {
+    pub fn name(self) -> &'static str {
+        match self {
+            ShaderType::FRAGMENT => "fragment",
+            ShaderType::VERTEX => "vertex",
+            ShaderType::GEOMETRY => "geometry",
+            ShaderType::TESS_CONTROL => "tessellation control",
+            ShaderType::TESS_EVALUATION => "tessellation evaluation",
+            ShaderType::COMPUTE => "compute",
+        }
+    }
 }
 
 impl Shader {
+    #[allow(unused)]
     pub fn new(shader_type: ShaderType, source_file: &str) -> Result<Self> {
+        Shader::with_defines(shader_type, source_file, &[])
+    }
+
+    /// compiles `source_file` after resolving its `#include`s, with `#define NAME VALUE` for each of `defines`
+    pub fn with_defines(shader_type: ShaderType, source_file: &str, defines: &[(&str, &str)]) -> Result<Self> {
+        let preprocessed = preprocessor::preprocess(Path::new(source_file), defines)?;
+        Shader::from_preprocessed(shader_type, source_file, defines, preprocessed)
+    }
 
+    /// compiles `preprocessed`, the output of running `source_file` through the preprocessor with `defines`
+    pub fn from_preprocessed(shader_type: ShaderType, source_file: &str, defines: &[(&str, &str)], preprocessed: Preprocessed) -> Result<Self> {
         let handle = unsafe {
             let shader = gl::CreateShader(shader_type as u32);
 
-            let shader_code = fs::read_to_string(source_file).with_context(|| format!("failed to read texture source file {}", source_file))?;
+            let shader_code = &preprocessed.source;
 
             let source_c_str = CString::new(shader_code.as_bytes()).with_context(|| format!("failed to convert shader source to c string {}", source_file))?;
 
@@ -35,20 +86,100 @@ impl Shader {
             gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut status);
 
             if status != (gl::TRUE as GLint) {
-                let mut log_len = 0;
-                gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut log_len);
-                let mut log_buf: Vec<u8> = Vec::with_capacity(log_len as usize);
-                log_buf.set_len((log_len as usize) - 1);
-                gl::GetShaderInfoLog(shader, log_len, std::ptr::null_mut(), log_buf.as_mut_ptr() as *mut GLchar);
+                let log = shader_info_log(shader);
+                gl::DeleteShader(shader);
 
-                return Err(anyhow!(String::from_utf8(log_buf).unwrap().to_string()));
+                return Err(anyhow::Error::new(CompileError::from_log(log, &preprocessed.files, &preprocessed.source)))
+                    .context(format!("failed to compile {} (source strings {})", source_file, preprocessed.file_legend()));
             }
             shader
         };
 
         info!("created shader #{}: {}", handle, source_file);
-        Ok(Shader { shader_type, handle })
+        Ok(Shader {
+            shader_type,
+            handle,
+            source_file: source_file.to_string(),
+            defines: defines.iter().map(|&(name, value)| (name.to_string(), value.to_string())).collect(),
+            source_files: preprocessed.files,
+            spirv: None,
+        })
+    }
+
+    /// loads a precompiled SPIR-V module and specializes `entry_point` with `constants` as (constant_id, value) pairs.
+    /// needs GL 4.6 or ARB_gl_spirv, the module must be built for OpenGL rather than Vulkan
+    pub fn from_spirv(shader_type: ShaderType, spirv_file: &str, entry_point: &str, constants: &[(u32, u32)]) -> Result<Self> {
+        if !supports(4, 6, "GL_ARB_gl_spirv") {
+            return Err(anyhow!("can't load {}, SPIR-V shaders need GL 4.6 or ARB_gl_spirv", spirv_file));
+        }
+
+        let spirv = fs::read(spirv_file).with_context(|| format!("failed to read SPIR-V module {}", spirv_file))?;
+        let magic = spirv.first_chunk::<4>().map(|magic| u32::from_le_bytes(*magic));
+        if spirv.len() % 4 != 0 || magic != Some(SPIRV_MAGIC) {
+            return Err(anyhow!("{} is not a little endian SPIR-V module", spirv_file));
+        }
+        let entry_point_c_str = CString::new(entry_point).with_context(|| format!("failed to convert entry point to c string {}", entry_point))?;
+        let (indices, values): (Vec<GLuint>, Vec<GLuint>) = constants.iter().copied().unzip();
+
+        let handle = unsafe {
+            let shader = gl::CreateShader(shader_type as u32);
+            gl::ShaderBinary(1, &shader, gl::SHADER_BINARY_FORMAT_SPIR_V, spirv.as_ptr() as *const c_void, spirv.len() as GLsizei);
+            gl::SpecializeShader(shader, entry_point_c_str.as_ptr(), constants.len() as GLuint, indices.as_ptr(), values.as_ptr());
+
+            let mut status = gl::FALSE as GLint;
+            gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut status);
+
+            if status != (gl::TRUE as GLint) {
+                let log = shader_info_log(shader);
+                gl::DeleteShader(shader);
+
+                // SPIR-V logs have no source lines to point at
+                return Err(anyhow::Error::new(CompileError::from_log(log, &[], ""))).context(format!("failed to specialize {} of {}", entry_point, spirv_file));
+            }
+            shader
+        };
+
+        info!("created shader #{}: {} ({})", handle, spirv_file, entry_point);
+        Ok(Shader {
+            shader_type,
+            handle,
+            source_file: spirv_file.to_string(),
+            defines: Vec::new(),
+            source_files: vec![PathBuf::from(spirv_file)],
+            spirv: Some(Specialization {
+                entry_point: entry_point.to_string(),
+                constants: constants.to_vec(),
+            }),
+        })
+    }
+
+    /// compiles the current sources again with the same defines, or reloads the SPIR-V module with the same specialization
+    #[allow(unused)]
+    pub fn recompile(&self) -> Result<Shader> {
+        if let Some(spirv) = &self.spirv {
+            return Shader::from_spirv(self.shader_type, &self.source_file, &spirv.entry_point, &spirv.constants);
+        }
+        let defines: Vec<(&str, &str)> = self.defines.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect();
+        Shader::with_defines(self.shader_type, &self.source_file, &defines)
+    }
+}
+
+/// one of the single stage shaders in `tests/fixtures/glsl/stages`
+#[cfg(test)]
+pub fn stage_fixture(shader_type: ShaderType, name: &str) -> Shader {
+    Shader::new(shader_type, &format!("tests/fixtures/glsl/stages/{}.glsl", name)).unwrap()
+}
+
+unsafe fn shader_info_log(shader: GLuint) -> String {
+    let mut log_len = 0;
+    gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut log_len);
+    if log_len == 0 {
+        return String::from("no info log");
     }
+    let mut log_buf = vec![0u8; log_len as usize];
+    let mut written = 0;
+    gl::GetShaderInfoLog(shader, log_len, &mut written, log_buf.as_mut_ptr() as *mut GLchar);
+    String::from_utf8_lossy(&log_buf[..written as usize]).into_owned()
 }
 
 impl Drop for Shader {
@@ -62,9 +193,51 @@ impl Drop for Shader {
 
 impl fmt::Display for Shader {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self.shader_type {
-            ShaderType::FRAGMENT => write!(f, "fragment shader #{}", self.handle),
-            ShaderType::VERTEX => write!(f, "vertex shader #{}", self.handle),
+        write!(f, "{} shader #{}", self.shader_type.name(), self.handle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::headless::gl_test_context;
+    use crate::app::renderer::buffer::{Buffer, BufferStorage, BufferTarget};
+    use crate::app::renderer::compute_program::{memory_barrier, Barrier, ComputeProgram};
+
+    #[test]
+    fn loads_and_specializes_spirv() {
+        let _context = gl_test_context();
+        let spirv = "tests/fixtures/spirv/fill.spv";
+
+        let shader = Shader::from_spirv(ShaderType::COMPUTE, spirv, "fill", &[(0, 3)]).unwrap();
+        let compute = ComputeProgram::new(&shader).unwrap();
+        assert_eq!(compute.work_group_size, [8, 1, 1]);
+
+        let buffer = Buffer::<u32>::with_len(BufferTarget::STORAGE, BufferStorage::DYNAMIC, 8).unwrap();
+        unsafe {
+            buffer.bind(0);
+            compute.dispatch(1, 1, 1).unwrap();
+            memory_barrier(&[Barrier::BUFFER_UPDATE]);
         }
+        assert_eq!(buffer.read(), (0..8).map(|i| i * 3).collect::<Vec<u32>>());
+
+        let error = Shader::from_spirv(ShaderType::COMPUTE, spirv, "main", &[]).err().unwrap();
+        assert!(format!("{:#}", error).contains("failed to specialize main"), "{:#}", error);
+        let error = Shader::from_spirv(ShaderType::COMPUTE, "tests/fixtures/spirv/fill.comp.glsl", "fill", &[]).err().unwrap();
+        assert!(error.to_string().contains("not a little endian SPIR-V module"), "{}", error);
+    }
+
+    #[test]
+    fn compile_errors_point_at_the_original_file() {
+        let _context = gl_test_context();
+
+        let error = Shader::with_defines(ShaderType::VERTEX, "tests/fixtures/shaderc/vertex.glsl", &[("BROKEN", "")]).err().unwrap();
+        let compile_error = error.downcast_ref::<CompileError>().unwrap();
+
+        // a syntax error, as Mesa reports some semantic errors against source string 0 whatever `#line` says
+        assert_eq!(compile_error.errors.len(), 1, "{}", compile_error);
+        assert_eq!(compile_error.errors[0].file, PathBuf::from("tests/fixtures/shaderc/broken.glsl"));
+        assert_eq!(compile_error.errors[0].line, 3);
+        assert!(compile_error.to_string().contains("3 | float broken() { return 1.0 }"), "{}", compile_error);
     }
 }
@@ -0,0 +1,277 @@
+//! turns driver compile logs into `ShaderError`s pointing at the original files.
+//!
+//! the preprocessor numbers every file as a `#line` source string, so log locations are
+//! `(source string, line)` pairs that index straight into `Preprocessed::files`. the formats are:
+//!
+//! - Mesa: `0:12(5): error: message`
+//! - NVIDIA: `0(12) : error C1008: message`
+//! - AMD and Intel on Windows: `ERROR: 0:12: message`
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShaderError {
+    pub file: PathBuf,
+    /// 1 based
+    pub line: usize,
+    /// 1 based, only Mesa reports columns
+    pub column: Option<usize>,
+    pub message: String,
+    /// the offending line as it was compiled, `None` if the log points outside the source
+    pub source_line: Option<String>,
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.column {
+            Some(column) => write!(f, "{}:{}:{}: {}", self.file.display(), self.line, column, self.message),
+            None => write!(f, "{}:{}: {}", self.file.display(), self.line, self.message),
+        }
+    }
+}
+
+/// every error in a failed compile, displayed with the offending source lines
+#[derive(Debug)]
+pub struct CompileError {
+    pub errors: Vec<ShaderError>,
+    /// log lines that aren't in `errors`: warnings, summaries and errors in no known format or source string
+    pub unparsed: Vec<String>,
+    /// the log as the driver wrote it, includes warnings and anything that didn't parse
+    pub log: String,
+}
+
+impl CompileError {
+    /// splits `log` into errors located in `files`, the shader's source strings, and everything else.
+    /// `source` is the preprocessed source that was compiled, the offending lines are copied out of it
+    pub fn from_log(log: String, files: &[PathBuf], source: &str) -> Self {
+        let source_lines = original_lines(source);
+        let mut errors = Vec::new();
+        let mut unparsed = Vec::new();
+        for line in log.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            match parse_line(line, files, &source_lines) {
+                Some(error) => errors.push(error),
+                None => unparsed.push(line.to_string()),
+            }
+        }
+        CompileError { errors, unparsed, log }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.errors.is_empty() {
+            return write!(f, "{}", self.log.trim_end());
+        }
+        for (index, error) in self.errors.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", error)?;
+
+            if let Some(text) = &error.source_line {
+                write!(f, "\n{}", source_context(text, error.line, error.column))?;
+            }
+        }
+        for line in &self.unparsed {
+            write!(f, "\n{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// the source line under its number, with a caret at the column or the whole line underlined
+fn source_context(text: &str, line: usize, column: Option<usize>) -> String {
+    let number = line.to_string();
+    let gutter = " ".repeat(number.len());
+    let marker = match column {
+        Some(column) => format!("{}^", " ".repeat(column.saturating_sub(1))),
+        None => {
+            let indent = text.len() - text.trim_start().len();
+            format!("{}{}", &text[..indent], "^".repeat(text.trim().len().max(1)))
+        }
+    };
+    format!("{} | {}\n{} | {}", number, text, gutter, marker)
+}
+
+/// every line of the preprocessed `source` keyed by the (source string, line) its `#line` directives give it
+fn original_lines(source: &str) -> HashMap<(usize, usize), &str> {
+    let mut lines = HashMap::new();
+    let (mut source_string, mut line_number) = (0, 1);
+    for text in source.lines() {
+        match text.split_whitespace().collect::<Vec<_>>()[..] {
+            ["#line", next_line, next_source_string] => {
+                line_number = next_line.parse().unwrap_or(line_number);
+                source_string = next_source_string.parse().unwrap_or(source_string);
+            }
+            _ => {
+                lines.insert((source_string, line_number), text);
+                line_number += 1;
+            }
+        }
+    }
+    lines
+}
+
+/// `None` for warnings, lines in no known format and source strings that aren't in `files`
+fn parse_line(line: &str, files: &[PathBuf], source_lines: &HashMap<(usize, usize), &str>) -> Option<ShaderError> {
+    let (source_string, line_number, column, message) = parse_mesa(line).or_else(|| parse_nvidia(line)).or_else(|| parse_amd(line))?;
+    Some(ShaderError {
+        file: files.get(source_string)?.clone(),
+        line: line_number,
+        column,
+        message: message.trim().to_string(),
+        source_line: source_lines.get(&(source_string, line_number)).map(|text| text.to_string()),
+    })
+}
+
+// 0:12(5): error: message
+fn parse_mesa(line: &str) -> Option<(usize, usize, Option<usize>, &str)> {
+    let (location, rest) = line.split_once(": ")?;
+    let message = rest.strip_prefix("error: ")?;
+    let (source_string, position) = location.split_once(':')?;
+    let (line_number, column) = position.strip_suffix(')')?.split_once('(')?;
+    Some((source_string.parse().ok()?, line_number.parse().ok()?, Some(column.parse().ok()?), message))
+}
+
+// 0(12) : error C1008: message
+fn parse_nvidia(line: &str) -> Option<(usize, usize, Option<usize>, &str)> {
+    let (location, rest) = line.split_once(" : ")?;
+    let (source_string, line_number) = location.strip_suffix(')')?.split_once('(')?;
+    let message = rest.strip_prefix("error")?;
+    // the error code is optional
+    let message = match message.trim_start().strip_prefix('C') {
+        Some(code) => code.split_once(": ")?.1,
+        None => message.strip_prefix(": ")?,
+    };
+    Some((source_string.parse().ok()?, line_number.parse().ok()?, None, message))
+}
+
+// ERROR: 0:12: message
+fn parse_amd(line: &str) -> Option<(usize, usize, Option<usize>, &str)> {
+    let rest = line.strip_prefix("ERROR: ")?;
+    let mut parts = rest.splitn(3, ':');
+    let source_string = parts.next()?.trim().parse().ok()?;
+    let line_number = parts.next()?.trim().parse().ok()?;
+    Some((source_string, line_number, None, parts.next()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files() -> Vec<PathBuf> {
+        vec![PathBuf::from("shaders/vertex.glsl"), PathBuf::from("shaders/per_frame_data.glsl")]
+    }
+
+    fn parse_log(log: &str, files: &[PathBuf]) -> Vec<ShaderError> {
+        CompileError::from_log(log.to_string(), files, "").errors
+    }
+
+    fn parse_log_with_source(log: &str, source: &str) -> Vec<ShaderError> {
+        CompileError::from_log(log.to_string(), &files(), source).errors
+    }
+
+    #[test]
+    fn parses_mesa_logs() {
+        let log = "0:12(5): error: `foo' undeclared\n1:3(1): warning: unused variable\n1:4(10): error: syntax error, unexpected ';'\n";
+
+        assert_eq!(
+            parse_log(log, &files()),
+            vec![
+                ShaderError {
+                    file: files()[0].clone(),
+                    line: 12,
+                    column: Some(5),
+                    message: "`foo' undeclared".to_string(),
+                    source_line: None,
+                },
+                ShaderError {
+                    file: files()[1].clone(),
+                    line: 4,
+                    column: Some(10),
+                    message: "syntax error, unexpected ';'".to_string(),
+                    source_line: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_nvidia_logs() {
+        let log = "0(12) : error C1008: undefined variable \"foo\"\n1(2) : warning C7533: global variable gl_FragColor is deprecated\n";
+
+        let errors = parse_log(log, &files());
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].to_string(), "shaders/vertex.glsl:12: undefined variable \"foo\"");
+    }
+
+    #[test]
+    fn parses_amd_logs() {
+        let log = "ERROR: 1:7: 'foo' : undeclared identifier \nWARNING: 0:1: extension not supported\nERROR: 1 compilation errors.  No code generated.\n";
+
+        let errors = parse_log(log, &files());
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].to_string(), "shaders/per_frame_data.glsl:7: 'foo' : undeclared identifier");
+    }
+
+    #[test]
+    fn renders_offending_source_lines() {
+        let file = PathBuf::from("shaders/common.glsl");
+        let source_line = Some("vec3 common_color() { return vec3(PI); }".to_string());
+        let error = CompileError {
+            errors: vec![
+                ShaderError {
+                    file: file.clone(),
+                    line: 3,
+                    column: Some(6),
+                    message: "first".to_string(),
+                    source_line: source_line.clone(),
+                },
+                ShaderError {
+                    file: file.clone(),
+                    line: 3,
+                    column: None,
+                    message: "second".to_string(),
+                    source_line,
+                },
+            ],
+            unparsed: Vec::new(),
+            log: String::new(),
+        };
+
+        let rendered = error.to_string();
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines[0], format!("{}:3:6: first", file.display()));
+        assert_eq!(lines[1], "3 | vec3 common_color() { return vec3(PI); }");
+        assert_eq!(lines[2], "  |      ^");
+        assert_eq!(lines[4], "3 | vec3 common_color() { return vec3(PI); }");
+        assert_eq!(lines[5], format!("  | {}", "^".repeat(40)));
+    }
+
+    #[test]
+    fn keeps_lines_that_are_not_located_errors() {
+        let log = "0:12(5): error: `foo' undeclared\n0:13(1): warning: unused variable\n7:1(1): error: in an unknown source string\nerror: linker gave up\n";
+        let error = CompileError::from_log(log.to_string(), &files(), "");
+
+        assert_eq!(error.errors.len(), 1);
+        assert_eq!(error.unparsed, ["0:13(1): warning: unused variable", "7:1(1): error: in an unknown source string", "error: linker gave up"]);
+        let rendered = error.to_string();
+        assert!(rendered.starts_with("shaders/vertex.glsl:12:5: `foo' undeclared\n"), "{}", rendered);
+        assert!(rendered.ends_with("\n7:1(1): error: in an unknown source string\nerror: linker gave up"), "{}", rendered);
+        assert!(CompileError::from_log(log.to_string(), &[], "").errors.is_empty());
+    }
+
+    #[test]
+    fn copies_source_lines_through_line_directives() {
+        let source = "#version 460\n#define FOO 1\n#line 2 0\nvoid main() {\n#line 1 1\nlayout(std140) uniform PerFrame {\n#line 3 0\n    foo;\n}\n";
+        let log = "0:3(5): error: `foo' undeclared\n1:1(1): error: bad layout\n0:9(1): error: past the end\n";
+
+        let errors = parse_log_with_source(log, source);
+        let lines: Vec<_> = errors.iter().map(|error| error.source_line.as_deref()).collect();
+        assert_eq!(lines, [Some("    foo;"), Some("layout(std140) uniform PerFrame {"), None]);
+    }
+}
@@ -0,0 +1,138 @@
+use log::warn;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// notices edits to shader sources by polling their modification times. polling a handful of files
+/// a few times a second is cheap, and unlike OS notifications it behaves the same everywhere,
+/// including editors that save by replacing the file
+pub struct ShaderWatcher {
+    files: Vec<WatchedFile>,
+    interval: Duration,
+    last_poll: Instant,
+}
+
+struct WatchedFile {
+    path: PathBuf,
+    /// when it was last seen modified, `None` if it has never been readable
+    modified: Option<SystemTime>,
+    /// it couldn't be read on the last poll, so a warning has been logged already
+    missing: bool,
+}
+
+impl ShaderWatcher {
+    pub fn new(interval: Duration) -> Self {
+        ShaderWatcher {
+            files: Vec::new(),
+            interval,
+            last_poll: Instant::now(),
+        }
+    }
+
+    pub fn watch(&mut self, path: impl AsRef<Path>) {
+        let path = path.as_ref().to_path_buf();
+        if self.files.iter().any(|file| file.path == path) {
+            return;
+        }
+        let mut file = WatchedFile {
+            path,
+            modified: None,
+            missing: false,
+        };
+        file.check();
+        self.files.push(file);
+    }
+
+    /// files modified since the last poll. returns nothing until `interval` has passed since the last check
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < self.interval {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+
+        self.files.iter_mut().filter_map(|file| file.check().then(|| file.path.clone())).collect()
+    }
+}
+
+impl WatchedFile {
+    /// true when the file has a new modification time. a missing file is usually mid save, so it's
+    /// warned about once and then waited for rather than reported
+    fn check(&mut self) -> bool {
+        match modified(&self.path) {
+            Ok(modified) => {
+                self.missing = false;
+                let changed = self.modified != Some(modified);
+                self.modified = Some(modified);
+                changed
+            }
+            Err(e) => {
+                if !self.missing {
+                    warn!("can't read modification time of {}: {}", self.path.display(), e);
+                    self.missing = true;
+                }
+                false
+            }
+        }
+    }
+}
+
+fn modified(path: &Path) -> io::Result<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    #[test]
+    fn reports_each_modification_once() {
+        let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/shader_watcher");
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("watched.glsl");
+        fs::write(&path, "void main() {}").unwrap();
+        let start = SystemTime::now() - Duration::from_secs(60);
+        File::options().write(true).open(&path).unwrap().set_modified(start).unwrap();
+
+        let mut watcher = ShaderWatcher::new(Duration::ZERO);
+        watcher.watch(&path);
+        watcher.watch(&path);
+        assert!(watcher.poll().is_empty());
+
+        File::options().write(true).open(&path).unwrap().set_modified(start + Duration::from_secs(1)).unwrap();
+        assert_eq!(watcher.poll(), vec![path.clone()]);
+        assert!(watcher.poll().is_empty());
+    }
+
+    #[test]
+    fn waits_for_missing_files_to_come_back() {
+        let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/shader_watcher");
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("replaced.glsl");
+        fs::write(&path, "void main() {}").unwrap();
+
+        let mut watcher = ShaderWatcher::new(Duration::ZERO);
+        watcher.watch(&path);
+        let modified = watcher.files[0].modified.unwrap();
+
+        fs::remove_file(&path).unwrap();
+        assert!(watcher.poll().is_empty());
+        assert!(watcher.files[0].missing);
+        assert!(watcher.poll().is_empty());
+
+        fs::write(&path, "void main() { }").unwrap();
+        File::options().write(true).open(&path).unwrap().set_modified(modified + Duration::from_secs(1)).unwrap();
+        assert_eq!(watcher.poll(), vec![path.clone()]);
+        assert!(!watcher.files[0].missing);
+    }
+
+    #[test]
+    fn waits_for_the_interval() {
+        let mut watcher = ShaderWatcher::new(Duration::from_secs(3600));
+        watcher.watch("shaders/vertex.glsl");
+        watcher.files[0].modified = None;
+
+        assert!(watcher.poll().is_empty());
+    }
+}
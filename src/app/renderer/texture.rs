@@ -1,41 +1,73 @@
 use anyhow::{anyhow, Result};
 use gl::types::*;
-use image::ImageReader;
-use log::info;
+use image::{DynamicImage, ImageReader};
+use log::{info, trace};
 use std::ffi::c_void;
 use std::fmt;
+use std::rc::Rc;
 
 use super::gl;
+use super::sampler::{Sampler, SamplerDescriptor};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextureDescriptor {
+    /// allocates the full mip chain and generates it from the image, otherwise only the base level exists
+    pub mipmaps: bool,
+    /// the texture's own sampling state, overridden while a `Sampler` is bound alongside it
+    pub sampler: SamplerDescriptor,
+}
+
+impl Default for TextureDescriptor {
+    fn default() -> Self {
+        TextureDescriptor {
+            mipmaps: true,
+            sampler: SamplerDescriptor::default(),
+        }
+    }
+}
 
 pub struct Texture {
     pub handle: u32,
+    #[allow(unused)]
+    pub levels: i32,
+    /// bound with the texture in place of its own sampling state
+    pub sampler: Option<Rc<Sampler>>,
 }
 
 impl Texture {
-    pub fn new(source_file: &str) -> Result<Self> {
-        let texture_id = unsafe {
-            let img = match ImageReader::open(source_file) {
-                Ok(img) => img,
-                Err(e) => return Err(anyhow!("failed to read source file {}", source_file).context(e)),
-            };
-            let img = match img.decode() {
-                Ok(img) => img,
-                Err(e) => return Err(anyhow!("failed to decode image source file {}", source_file).context(e)),
-            };
+    pub fn new(source_file: &str, descriptor: &TextureDescriptor) -> Result<Self> {
+        let img = match ImageReader::open(source_file) {
+            Ok(img) => img,
+            Err(e) => return Err(anyhow!("failed to read source file {}", source_file).context(e)),
+        };
+        let img = match img.decode() {
+            Ok(img) => img,
+            Err(e) => return Err(anyhow!("failed to decode image source file {}", source_file).context(e)),
+        };
 
-            let img = img.into_rgb8();
+        let texture = Texture::from_image(&img, descriptor)?;
+        info!("loaded {} from {}", texture, source_file);
+
+        return Ok(texture);
+    }
 
-            let width: i32 = img.width() as i32;
-            let height: i32 = img.height() as i32;
+    pub fn from_image(img: &DynamicImage, descriptor: &TextureDescriptor) -> Result<Self> {
+        let img = img.to_rgba8();
+        let width: i32 = img.width() as i32;
+        let height: i32 = img.height() as i32;
+        if width == 0 || height == 0 {
+            return Err(anyhow!("can't create a texture from a {}x{} image", width, height));
+        }
+        let levels = match descriptor.mipmaps {
+            true => mip_levels(width, height),
+            false => 1,
+        };
 
+        let texture_id = unsafe {
             let mut tex: GLuint = 0;
-            // create texture 2D
             gl::CreateTextures(gl::TEXTURE_2D, 1, &mut tex);
-            gl::TextureParameteri(tex, gl::TEXTURE_MAX_LEVEL, 0);
-            // these as statements are suspect
-            gl::TextureParameteri(tex, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
-            gl::TextureParameteri(tex, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
-            gl::TextureStorage2D(tex, 1, gl::RGB8, width, height);
+            descriptor.sampler.apply(tex, gl::TextureParameteri, gl::TextureParameterf, gl::TextureParameterfv);
+            gl::TextureStorage2D(tex, levels, gl::RGBA8, width, height);
             gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
             gl::TextureSubImage2D(
                 tex,
@@ -44,24 +76,66 @@ impl Texture {
                 0,
                 width,
                 height,
-                gl::RGB,
+                gl::RGBA,
                 gl::UNSIGNED_BYTE,
                 (&img as &[u8]).as_ptr() as *const c_void,
             );
+            if levels > 1 {
+                gl::GenerateTextureMipmap(tex);
+            }
 
             tex
         };
-        info!("creating texture #{} from {}", texture_id, source_file);
+        info!("creating {}x{} texture #{} with {} levels", width, height, texture_id, levels);
+
+        return Ok(Texture {
+            handle: texture_id,
+            levels,
+            sampler: None,
+        });
+    }
+
+    /// single level texture with undefined contents, for rendering into, e.g. as a framebuffer attachment
+    pub fn with_storage(width: i32, height: i32, internal_format: GLenum) -> Result<Self> {
+        let texture_id = unsafe {
+            let mut tex: GLuint = 0;
+            gl::CreateTextures(gl::TEXTURE_2D, 1, &mut tex);
+            gl::TextureParameteri(tex, gl::TEXTURE_MAX_LEVEL, 0);
+            gl::TextureParameteri(tex, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TextureParameteri(tex, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::TextureParameteri(tex, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TextureParameteri(tex, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TextureStorage2D(tex, 1, internal_format, width, height);
+            tex
+        };
+        info!("creating {}x{} texture #{} with format {:#x}", width, height, texture_id, internal_format);
+
+        return Ok(Texture {
+            handle: texture_id,
+            levels: 1,
+            sampler: None,
+        });
+    }
 
-        return Ok(Texture { handle: texture_id });
+    /// samples with `sampler` rather than the texture's own parameters
+    pub fn with_sampler(mut self, sampler: Rc<Sampler>) -> Self {
+        self.sampler = Some(sampler);
+        self
     }
 
-    pub unsafe fn bind(&self) {
-        info!("binding {}", self);
-        gl::BindTextures(0, 1, &self.handle);
+    /// binds the texture to `unit` along with its sampler, or unbinds whatever sampler was left there
+    pub unsafe fn bind(&self, unit: u32) {
+        trace!("binding {} to unit {}", self, unit);
+        gl::BindTextures(unit, 1, &self.handle);
+        gl::BindSampler(unit, self.sampler.as_ref().map_or(0, |sampler| sampler.handle));
     }
 }
 
+/// levels in a full mip chain, down to 1x1
+pub fn mip_levels(width: i32, height: i32) -> i32 {
+    32 - (width.max(height).max(1) as u32).leading_zeros() as i32
+}
+
 impl Drop for Texture {
     fn drop(&mut self) {
         info!("deleting {}", self);
@@ -76,3 +150,81 @@ impl fmt::Display for Texture {
         write!(f, "texture #{}", self.handle)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::headless::gl_test_context;
+    use crate::app::renderer::sampler::{Filter, Wrap};
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn mip_chain_goes_down_to_one_pixel() {
+        assert_eq!(mip_levels(1, 1), 1);
+        assert_eq!(mip_levels(2, 1), 2);
+        assert_eq!(mip_levels(256, 256), 9);
+        assert_eq!(mip_levels(300, 17), 9);
+        assert_eq!(mip_levels(512, 1024), 11);
+    }
+
+    #[test]
+    fn textures_generate_mipmaps_and_share_samplers() {
+        let _context = gl_test_context();
+        let checker = RgbaImage::from_fn(2, 2, |x, y| match (x + y) % 2 {
+            0 => Rgba([0, 0, 0, 255]),
+            _ => Rgba([255, 255, 255, 255]),
+        });
+        let image = DynamicImage::ImageRgba8(checker);
+
+        let texture = Texture::from_image(&image, &TextureDescriptor::default()).unwrap();
+        assert_eq!(texture.levels, 2);
+        // the 1x1 level averages the checker to grey
+        let mut pixel = [0u8; 4];
+        unsafe {
+            gl::GetTextureImage(texture.handle, 1, gl::RGBA, gl::UNSIGNED_BYTE, 4, pixel.as_mut_ptr() as *mut _);
+        }
+        assert!((126..=129).contains(&pixel[0]), "{:?}", pixel);
+
+        let single = TextureDescriptor {
+            mipmaps: false,
+            ..Default::default()
+        };
+        assert_eq!(Texture::from_image(&image, &single).unwrap().levels, 1);
+
+        let sampler = Rc::new(Sampler::new(SamplerDescriptor {
+            mag_filter: Filter::NEAREST,
+            mipmap_filter: None,
+            wrap_s: Wrap::CLAMP_TO_BORDER,
+            max_anisotropy: 1000.0,
+            border_color: [1.0, 0.0, 0.0, 1.0],
+            ..Default::default()
+        }));
+        let first = Texture::from_image(&image, &TextureDescriptor::default()).unwrap().with_sampler(sampler.clone());
+        let second = Texture::from_image(&image, &single).unwrap().with_sampler(sampler.clone());
+        assert_eq!(Rc::strong_count(&sampler), 3);
+
+        let (mut min_filter, mut wrap_s, mut anisotropy, mut border_color, mut bound) = (0, 0, 0.0, [0.0f32; 4], 0);
+        unsafe {
+            gl::GetSamplerParameteriv(sampler.handle, gl::TEXTURE_MIN_FILTER, &mut min_filter);
+            gl::GetSamplerParameteriv(sampler.handle, gl::TEXTURE_WRAP_S, &mut wrap_s);
+            gl::GetSamplerParameterfv(sampler.handle, gl::TEXTURE_MAX_ANISOTROPY, &mut anisotropy);
+            gl::GetSamplerParameterfv(sampler.handle, gl::TEXTURE_BORDER_COLOR, border_color.as_mut_ptr());
+
+            second.bind(1);
+            gl::ActiveTexture(gl::TEXTURE1);
+            gl::GetIntegerv(gl::SAMPLER_BINDING, &mut bound);
+            assert_eq!(bound as u32, sampler.handle);
+            // a texture without a sampler unbinds the one left on the unit
+            texture.bind(1);
+            gl::GetIntegerv(gl::SAMPLER_BINDING, &mut bound);
+            assert_eq!(bound, 0);
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+        assert_eq!(min_filter as u32, gl::LINEAR);
+        assert_eq!(wrap_s as u32, gl::CLAMP_TO_BORDER);
+        // clamped to the driver's limit
+        assert!((1.0..1000.0).contains(&anisotropy), "{}", anisotropy);
+        assert_eq!(border_color, [1.0, 0.0, 0.0, 1.0]);
+        drop(first);
+    }
+}
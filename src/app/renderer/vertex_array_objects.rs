@@ -1,9 +1,11 @@
 use anyhow::Result;
 use gl::types::*;
-use log::info;
+use log::{info, trace};
 use std::fmt;
 
+use super::buffer::Buffer;
 use super::gl;
+use super::index_buffer::IndexBuffer;
 
 pub struct VertexArrayObjects {
     pub handle: u32,
@@ -20,10 +22,26 @@ impl VertexArrayObjects {
         return Ok(VertexArrayObjects { handle: vao });
     }
 
+    // bound once per draw call, so keep it out of the info log
     pub unsafe fn bind(&self) {
-        info!("binding {}", self);
+        trace!("binding {}", self);
         gl::BindVertexArray(self.handle);
     }
+
+    pub unsafe fn vertex_buffer<T: Copy>(&self, binding: u32, buffer: &Buffer<T>) {
+        gl::VertexArrayVertexBuffer(self.handle, binding, buffer.handle, 0, size_of::<T>() as GLsizei);
+    }
+
+    /// float attribute read from the vertex buffer attached at `binding`
+    pub unsafe fn attribute(&self, location: u32, binding: u32, components: GLint, offset: u32) {
+        gl::EnableVertexArrayAttrib(self.handle, location);
+        gl::VertexArrayAttribFormat(self.handle, location, components, gl::FLOAT, gl::FALSE, offset);
+        gl::VertexArrayAttribBinding(self.handle, location, binding);
+    }
+
+    pub unsafe fn element_buffer(&self, index_buffer: &IndexBuffer) {
+        gl::VertexArrayElementBuffer(self.handle, index_buffer.handle());
+    }
 }
 
 impl Drop for VertexArrayObjects {
@@ -0,0 +1,309 @@
+//! checks every shader under a directory (`shaders/` by default) compiles, without a GL context or GPU.
+//!
+//! each root shader (a file with `#version` before anything but comments) goes through the same preprocessor as the renderer,
+//! then naga's GLSL front end and validator. files without `#version` are headers and are checked where
+//! they're included. the stage comes from a `_` or `.` separated word of the file name: `vertex`/`vert`,
+//! `fragment`/`frag` or `compute`/`comp`, so `shadow_frag.glsl` and `blur.comp.glsl` both work. every feature the shader tests with `#ifdef`, `#ifndef` or `defined()` is checked
+//! off, on by itself, and all on together.
+//!
+//! errors are printed as `file:line: message` against the original files, and the exit code is
+//! non-zero if anything failed, so it can gate merges.
+//!
+//! naga's GLSL support doesn't cover everything drivers accept, combined samplers like `sampler2D` and
+//! geometry or tessellation stages among them. variants that hit something naga hasn't implemented are
+//! listed as unchecked warnings rather than failures, so this catches most mistakes early but can't prove
+//! a shader compiles everywhere. pass `--strict` to fail on unchecked variants too.
+//!
+//! usage: `threed-shaderc [--strict] [directory]`
+
+use anyhow::{anyhow, Context, Result};
+use naga::front::glsl::{ErrorKind, Frontend, Options};
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use naga::{ShaderStage, SourceLocation};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[allow(unused)]
+#[path = "../app/renderer/preprocessor.rs"]
+mod preprocessor;
+use preprocessor::{preprocess, version_line, Preprocessed};
+
+const DEFAULT_SHADER_DIRECTORY: &str = "shaders";
+
+fn main() -> ExitCode {
+    let (flags, paths): (Vec<String>, Vec<String>) = std::env::args().skip(1).partition(|arg| arg.starts_with("--"));
+    let strict = flags.iter().any(|flag| flag == "--strict");
+    if let Some(flag) = flags.iter().find(|flag| *flag != "--strict") {
+        eprintln!("error: unknown flag {}, usage: threed-shaderc [--strict] [directory]", flag);
+        return ExitCode::FAILURE;
+    }
+    let directory = paths.into_iter().next().unwrap_or(DEFAULT_SHADER_DIRECTORY.to_string());
+
+    match check_directory(Path::new(&directory)) {
+        Ok(report) => {
+            for warning in &report.unchecked {
+                eprintln!("warning: not checked, {}", warning);
+            }
+            for error in &report.errors {
+                eprintln!("{}", error);
+            }
+            println!(
+                "checked {} variants of {} shaders in {}, {} failed, {} unchecked",
+                report.variants,
+                report.shaders,
+                directory,
+                report.errors.len(),
+                report.unchecked.len()
+            );
+            match report.passed(strict) {
+                true => ExitCode::SUCCESS,
+                false => ExitCode::FAILURE,
+            }
+        }
+        Err(e) => {
+            eprintln!("error: {:?}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Report {
+    shaders: usize,
+    variants: usize,
+    /// one `file:line: message` per failed variant
+    errors: Vec<String>,
+    /// variants using GLSL naga can't parse yet, same format as `errors`
+    unchecked: Vec<String>,
+}
+
+impl Report {
+    /// no variant failed, and with `strict` none went unchecked either
+    fn passed(&self, strict: bool) -> bool {
+        self.errors.is_empty() && (!strict || self.unchecked.is_empty())
+    }
+}
+
+enum Checked {
+    Valid,
+    /// naga reported something it hasn't implemented, not a mistake in the shader
+    Unsupported(String),
+}
+
+fn check_directory(directory: &Path) -> Result<Report> {
+    let mut files = Vec::new();
+    collect_glsl_files(directory, &mut files)?;
+    files.sort();
+
+    let mut report = Report::default();
+    for file in files {
+        let source = fs::read_to_string(&file).with_context(|| format!("failed to read {}", file.display()))?;
+        if version_line(&source).is_none() {
+            continue;
+        }
+        report.shaders += 1;
+
+        let Some(stage) = stage_from_file_name(&file) else {
+            report.errors.push(format!("{}:1: can't tell the shader stage from the file name", file.display()));
+            continue;
+        };
+        let features = match preprocess(&file, &[]) {
+            Ok(preprocessed) => features(&preprocessed.source),
+            Err(e) => {
+                report.errors.push(format!("{}: {:#}", file.display(), e));
+                continue;
+            }
+        };
+
+        for variant in variants(&features) {
+            report.variants += 1;
+            let defines = match variant.is_empty() {
+                true => String::new(),
+                false => format!(" (with {})", variant.join(", ")),
+            };
+            match check_variant(&file, stage, &variant) {
+                Ok(Checked::Valid) => (),
+                Ok(Checked::Unsupported(message)) => report.unchecked.push(format!("{}{}", message, defines)),
+                Err(e) => report.errors.push(format!("{}{}", e, defines)),
+            }
+        }
+    }
+    Ok(report)
+}
+
+fn collect_glsl_files(directory: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(directory).with_context(|| format!("failed to read shader directory {}", directory.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_glsl_files(&path, files)?;
+        } else if path.extension().is_some_and(|extension| extension == "glsl") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// the stage named by the first `_` or `.` separated word of the file name that names one
+fn stage_from_file_name(file: &Path) -> Option<ShaderStage> {
+    let name = file.file_stem()?.to_str()?.to_lowercase();
+    name.split(['_', '.']).find_map(|word| match word {
+        "vertex" | "vert" => Some(ShaderStage::Vertex),
+        "fragment" | "frag" => Some(ShaderStage::Fragment),
+        "compute" | "comp" => Some(ShaderStage::Compute),
+        _ => None,
+    })
+}
+
+/// names tested by `#ifdef NAME`, `#ifndef NAME` and `defined(NAME)`
+fn features(source: &str) -> BTreeSet<String> {
+    let mut features = BTreeSet::new();
+    for line in source.lines().map(str::trim_start).filter(|line| line.starts_with('#')) {
+        let directive = line[1..].trim_start();
+        if let Some(name) = directive.strip_prefix("ifdef").or_else(|| directive.strip_prefix("ifndef")) {
+            features.extend(name.split_whitespace().next().map(str::to_string));
+        }
+        for (index, _) in directive.match_indices("defined") {
+            let rest = directive[index + "defined".len()..].trim_start();
+            let rest = rest.strip_prefix('(').unwrap_or(rest).trim_start();
+            let name: String = rest.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+            if !name.is_empty() {
+                features.insert(name);
+            }
+        }
+    }
+    features
+}
+
+/// no features, each feature alone, then all of them
+fn variants(features: &BTreeSet<String>) -> Vec<Vec<String>> {
+    let mut variants = vec![Vec::new()];
+    variants.extend(features.iter().map(|feature| vec![feature.clone()]));
+    if features.len() > 1 {
+        variants.push(features.iter().cloned().collect());
+    }
+    variants
+}
+
+/// parses and validates one variant, the error is `file:line: message` in the original sources
+fn check_variant(file: &Path, stage: ShaderStage, defines: &[String]) -> Result<Checked> {
+    let defines: Vec<(&str, &str)> = defines.iter().map(|name| (name.as_str(), "")).collect();
+    let preprocessed = preprocess(file, &defines).map_err(|e| anyhow!("{}: {:#}", file.display(), e))?;
+
+    let module = match Frontend::default().parse(&Options::from(stage), &preprocessed.source) {
+        Ok(module) => module,
+        Err(errors) => {
+            let messages: Vec<String> = errors.errors.iter().map(|error| located(&preprocessed, error.location(&preprocessed.source), &error.kind)).collect();
+            if errors.errors.iter().all(|error| matches!(error.kind, ErrorKind::NotImplemented(_))) {
+                return Ok(Checked::Unsupported(messages.join("\n")));
+            }
+            return Err(anyhow!(messages.join("\n")));
+        }
+    };
+
+    Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|error| {
+            let location = error.location(&preprocessed.source);
+            anyhow!(located(&preprocessed, location, &format!("{:#}", anyhow::Error::from(error))))
+        })?;
+    Ok(Checked::Valid)
+}
+
+fn located(preprocessed: &Preprocessed, location: Option<SourceLocation>, message: &dyn std::fmt::Display) -> String {
+    let (file, line) = match location {
+        Some(location) => original_location(preprocessed, location.line_number as usize),
+        None => (preprocessed.files[0].as_path(), 1),
+    };
+    format!("{}:{}: {}", file.display(), line, message)
+}
+
+/// the file and line that line `output_line` (1 based) of the preprocessed source came from, following `#line` directives
+fn original_location(preprocessed: &Preprocessed, output_line: usize) -> (&Path, usize) {
+    let mut file_number = 0;
+    let mut line = 1;
+    for text in preprocessed.source.lines().take(output_line - 1) {
+        let directive: Vec<&str> = text.split_whitespace().collect();
+        match directive[..] {
+            ["#line", next_line, file] => {
+                line = next_line.parse().unwrap_or(line);
+                file_number = file.parse().unwrap_or(file_number);
+            }
+            _ => line += 1,
+        }
+    }
+    let file = preprocessed.files.get(file_number).unwrap_or(&preprocessed.files[0]);
+    (file.as_path(), line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+    }
+
+    #[test]
+    fn bundled_shaders_are_valid() {
+        let report = check_directory(&PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(DEFAULT_SHADER_DIRECTORY)).unwrap();
+
+        assert!(report.errors.is_empty(), "{:#?}", report.errors);
+        assert_eq!(report.shaders, 2);
+        // without and with TEXTURED
+        assert_eq!(report.variants, 4);
+        // naga has no combined samplers, so the textured fragment shader can't be checked
+        assert_eq!(report.unchecked.len(), 1, "{:#?}", report.unchecked);
+        assert!(report.unchecked[0].contains("fragment.glsl"), "{}", report.unchecked[0]);
+        assert!(report.passed(false));
+        assert!(!report.passed(true));
+    }
+
+    #[test]
+    fn reports_errors_at_their_original_line() {
+        let report = check_directory(&fixture("shaderc")).unwrap();
+
+        assert_eq!(report.shaders, 2, "{:#?}", report);
+        assert_eq!(report.errors.len(), 1, "{:#?}", report.errors);
+        let error = &report.errors[0];
+        // the mistake is in the include, and only compiled with BROKEN
+        assert!(error.contains("broken.glsl:3: "), "{}", error);
+        assert!(error.ends_with("(with BROKEN)"), "{}", error);
+        assert!(!report.passed(false));
+    }
+
+    #[test]
+    fn stage_comes_from_whole_words_of_the_file_name() {
+        let stage = |name: &str| stage_from_file_name(Path::new(name));
+
+        assert_eq!(stage("shaders/vertex.glsl"), Some(ShaderStage::Vertex));
+        assert_eq!(stage("shadow_frag.glsl"), Some(ShaderStage::Fragment));
+        assert_eq!(stage("blur.comp.glsl"), Some(ShaderStage::Compute));
+        assert_eq!(stage("convert_comp.glsl"), Some(ShaderStage::Compute));
+        assert_eq!(stage("Convert_Vertices.glsl"), None);
+        assert_eq!(stage("per_frame_data.glsl"), None);
+    }
+
+    #[test]
+    fn follows_line_directives() {
+        let preprocessed = preprocess(&fixture("glsl/main.glsl"), &[("USE_COLOR", "1")]).unwrap();
+        let line_of = |text: &str| preprocessed.source.lines().position(|line| line.contains(text)).unwrap() + 1;
+
+        let (file, line) = original_location(&preprocessed, line_of("const float PI"));
+        assert!(file.ends_with("nested/constants.glsl"));
+        assert_eq!(line, 1);
+        let (file, line) = original_location(&preprocessed, line_of("void main"));
+        assert!(file.ends_with("glsl/main.glsl"));
+        assert_eq!(line, 3);
+    }
+
+    #[test]
+    fn finds_features_and_their_variants() {
+        let features = features("#ifdef TEXTURED\n#endif\n#  ifndef SKINNING\n#if defined(WIREFRAME) && defined SHADOWS\n");
+
+        assert_eq!(features.iter().collect::<Vec<_>>(), ["SHADOWS", "SKINNING", "TEXTURED", "WIREFRAME"]);
+        assert_eq!(variants(&features).len(), 6);
+        assert_eq!(variants(&BTreeSet::new()), vec![Vec::<String>::new()]);
+    }
+}
@@ -19,7 +19,18 @@ fn main() {
         Err(e) => error!("failed to get current exe path: {e}"),
     }
 
-    match app::main(app::ApplicationConfig {}) {
+    // `threed [--scene <scene.ron>] [--headless <output.png>]`, headless renders one frame without a window
+    let args: Vec<String> = env::args().collect();
+    let option = |name: &str| args.iter().position(|arg| arg == name).and_then(|i| args.get(i + 1)).cloned();
+
+    let headless = option("--headless").map(|output| app::HeadlessConfig {
+        width: 800,
+        height: 600,
+        output,
+    });
+    let scene = option("--scene").unwrap_or_else(|| app::DEFAULT_SCENE.to_string());
+
+    match app::main(app::ApplicationConfig { headless, scene }) {
         Ok(_) => info!("app closed gracefully"),
         Err(e) => error!("app ended in error: {:?}", e),
     }
@@ -0,0 +1,6 @@
+// licence header
+
+/* block
+   comment */
+#version 450 core
+void main() {}
@@ -0,0 +1,3 @@
+// common.glsl
+#include "nested/constants.glsl"
+vec3 common_color() { return vec3(PI); }
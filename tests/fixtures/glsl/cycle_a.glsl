@@ -0,0 +1,3 @@
+#version 450 core
+#include "cycle_b.glsl"
+void main() {}
@@ -0,0 +1,4 @@
+#version 450 core
+
+#include "does_not_exist.glsl"
+void main() {}
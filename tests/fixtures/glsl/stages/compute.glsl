@@ -0,0 +1,13 @@
+#version 450 core
+layout (local_size_x=8) in;
+
+layout (std430, binding=0) buffer values {
+  uint data[];
+};
+
+void main() {
+  // the last work group may run past the end of the buffer
+  if (gl_GlobalInvocationID.x < data.length()) {
+    data[gl_GlobalInvocationID.x] = gl_GlobalInvocationID.x * gl_GlobalInvocationID.x;
+  }
+}
@@ -0,0 +1,6 @@
+#version 450 core
+layout (location=0) out vec4 out_FragColor;
+
+void main() {
+  out_FragColor = vec4(1.0);
+}
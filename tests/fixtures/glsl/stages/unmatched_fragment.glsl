@@ -0,0 +1,8 @@
+#version 450 core
+// reads a varying the vertex fixture never writes, so linking fails
+in vec3 color;
+layout (location=0) out vec4 out_FragColor;
+
+void main() {
+  out_FragColor = vec4(color, 1.0);
+}
@@ -0,0 +1,4 @@
+#version 450 core
+#include "common.glsl"
+#include "nested/constants.glsl"
+void main() {}
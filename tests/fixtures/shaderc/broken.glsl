@@ -0,0 +1,4 @@
+// only compiled with BROKEN defined
+#ifdef BROKEN
+float broken() { return 1.0 }
+#endif
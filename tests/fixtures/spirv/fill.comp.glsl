@@ -0,0 +1,13 @@
+#version 450 core
+// fill.spv is this shader as SPIR-V for OpenGL, with the entry point named `fill`
+layout (local_size_x=8) in;
+
+layout (constant_id=0) const uint SCALE = 1;
+
+layout (std430, binding=0) buffer values {
+  uint data[];
+};
+
+void main() {
+  data[gl_GlobalInvocationID.x] = gl_GlobalInvocationID.x * SCALE;
+}
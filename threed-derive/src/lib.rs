@@ -0,0 +1,145 @@
+//! derives for `threed`'s renderer.
+//!
+//! `Std140` and `Std430` implement `GlslBlock` for a `#[repr(C)]` struct and add compile time assertions
+//! that every field sits at the offset the GLSL layout rules give it. Fields whose name starts with `_`
+//! are treated as explicit padding: they are skipped in the checks and in the generated GLSL.
+//!
+//! the generated code refers to `GlslField`, `GlslBlock` and `align_up` in `crate::app::renderer::layout`,
+//! another module providing them can be named with `#[glsl(layout = path::to::layout)]`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields};
+
+#[proc_macro_derive(Std140, attributes(glsl))]
+pub fn derive_std140(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input, Layout::Std140).unwrap_or_else(|e| e.to_compile_error()).into()
+}
+
+#[proc_macro_derive(Std430, attributes(glsl))]
+pub fn derive_std430(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input, Layout::Std430).unwrap_or_else(|e| e.to_compile_error()).into()
+}
+
+#[derive(Clone, Copy)]
+enum Layout {
+    Std140,
+    Std430,
+}
+
+impl Layout {
+    fn name(self) -> &'static str {
+        match self {
+            Layout::Std140 => "std140",
+            Layout::Std430 => "std430",
+        }
+    }
+
+    // std140 rounds the alignment of a structure up to that of a vec4, std430 doesn't
+    fn min_struct_align(self) -> usize {
+        match self {
+            Layout::Std140 => 16,
+            Layout::Std430 => 1,
+        }
+    }
+}
+
+fn expand(input: &DeriveInput, layout: Layout) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let layout_name = layout.name();
+    let min_struct_align = layout.min_struct_align();
+
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&input.generics, "GLSL blocks can't be generic"));
+    }
+    let layout_path = layout_path(input)?;
+    if !is_repr_c(input)? {
+        return Err(syn::Error::new_spanned(name, format!("{} blocks must be #[repr(C)] so the field order is kept", layout_name)));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(syn::Error::new_spanned(name, "GLSL blocks need named fields")),
+        },
+        _ => return Err(syn::Error::new_spanned(name, "GLSL blocks must be structs")),
+    };
+
+    let members: Vec<_> = fields
+        .iter()
+        .filter_map(|field| {
+            let ident = field.ident.as_ref().unwrap();
+            (!ident.to_string().starts_with('_')).then_some((ident, &field.ty))
+        })
+        .collect();
+
+    let member_entries = members.iter().map(|(ident, ty)| {
+        let field_name = ident.to_string();
+        quote! { (<#ty as #layout_path::GlslField>::GLSL_TYPE, #field_name) }
+    });
+
+    let offset_checks = members.iter().map(|(ident, ty)| {
+        let message = format!("`{}::{}` is not at its {} offset, add explicit `_` padding before it", name, ident, layout_name);
+        quote! {
+            offset = #layout_path::align_up(offset, <#ty as #layout_path::GlslField>::ALIGN);
+            assert!(::core::mem::offset_of!(#name, #ident) == offset, #message);
+            offset += <#ty as #layout_path::GlslField>::SIZE;
+            if <#ty as #layout_path::GlslField>::ALIGN > align {
+                align = <#ty as #layout_path::GlslField>::ALIGN;
+            }
+        }
+    });
+
+    let size_message = format!("size of `{}` is not padded to its {} alignment, add trailing `_` padding", name, layout_name);
+
+    Ok(quote! {
+        impl #layout_path::GlslBlock for #name {
+            const LAYOUT: &'static str = #layout_name;
+            const MEMBERS: &'static [(&'static str, &'static str)] = &[#(#member_entries),*];
+        }
+
+        const _: () = {
+            let mut offset: usize = 0;
+            let mut align: usize = #min_struct_align;
+            #(#offset_checks)*
+            assert!(::core::mem::size_of::<#name>() == #layout_path::align_up(offset, align), #size_message);
+        };
+    })
+}
+
+/// the module named by `#[glsl(layout = ...)]`, `crate::app::renderer::layout` without one
+fn layout_path(input: &DeriveInput) -> syn::Result<syn::Path> {
+    let mut path = syn::parse_quote!(crate::app::renderer::layout);
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("glsl")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("layout") {
+                path = meta.value()?.parse()?;
+                Ok(())
+            } else {
+                Err(meta.error("expected `layout = path::to::layout`"))
+            }
+        })?;
+    }
+    Ok(path)
+}
+
+fn is_repr_c(input: &DeriveInput) -> syn::Result<bool> {
+    let mut repr_c = false;
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("repr")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("C") {
+                repr_c = true;
+            }
+            // skip the arguments of e.g. `align(16)`
+            if meta.input.peek(syn::token::Paren) {
+                let _arguments;
+                syn::parenthesized!(_arguments in meta.input);
+            }
+            Ok(())
+        })?;
+    }
+    Ok(repr_c)
+}
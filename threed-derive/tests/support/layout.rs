@@ -0,0 +1,28 @@
+// the parts of threed's `layout` module the derives refer to, for the UI tests
+
+pub trait GlslField {
+    const GLSL_TYPE: &'static str;
+    const ALIGN: usize;
+    const SIZE: usize;
+}
+
+pub trait GlslBlock: Copy {
+    const LAYOUT: &'static str;
+    const MEMBERS: &'static [(&'static str, &'static str)];
+}
+
+pub const fn align_up(offset: usize, align: usize) -> usize {
+    offset.div_ceil(align) * align
+}
+
+impl GlslField for f32 {
+    const GLSL_TYPE: &'static str = "float";
+    const ALIGN: usize = 4;
+    const SIZE: usize = 4;
+}
+
+impl GlslField for [f32; 3] {
+    const GLSL_TYPE: &'static str = "vec3";
+    const ALIGN: usize = 16;
+    const SIZE: usize = 12;
+}
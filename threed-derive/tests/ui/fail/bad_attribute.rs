@@ -0,0 +1,13 @@
+#[path = "../../support/layout.rs"]
+mod layout;
+
+use threed_derive::Std140;
+
+#[repr(C)]
+#[derive(Clone, Copy, Std140)]
+#[glsl(module = crate::layout)]
+struct Light {
+    intensity: f32,
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+#[path = "../../support/layout.rs"]
+mod layout;
+
+use threed_derive::Std140;
+
+// std140 puts the vec3 at 16, Rust at 4
+#[repr(C)]
+#[derive(Clone, Copy, Std140)]
+#[glsl(layout = crate::layout)]
+struct Light {
+    intensity: f32,
+    position: [f32; 3],
+}
+
+fn main() {}
@@ -0,0 +1,14 @@
+#[path = "../../support/layout.rs"]
+mod layout;
+
+use threed_derive::Std430;
+
+// GLSL bools are 4 bytes, Rust's are 1
+#[repr(C)]
+#[derive(Clone, Copy, Std430)]
+#[glsl(layout = crate::layout)]
+struct Flags {
+    enabled: bool,
+}
+
+fn main() {}
@@ -0,0 +1,28 @@
+#[path = "../../support/layout.rs"]
+mod layout;
+
+use layout::GlslBlock;
+use threed_derive::{Std140, Std430};
+
+#[repr(C)]
+#[derive(Clone, Copy, Std140)]
+#[glsl(layout = crate::layout)]
+struct Light {
+    intensity: f32,
+    _padding: [u32; 3],
+    position: [f32; 3],
+    range: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Std430)]
+#[glsl(layout = layout)]
+struct Particle {
+    mass: f32,
+}
+
+fn main() {
+    assert_eq!(Light::LAYOUT, "std140");
+    assert_eq!(Light::MEMBERS, &[("float", "intensity"), ("vec3", "position"), ("float", "range")]);
+    assert_eq!(Particle::MEMBERS, &[("float", "mass")]);
+}